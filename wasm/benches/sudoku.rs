@@ -6,32 +6,32 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("gen 28", |b| {
         b.iter(|| {
             generate_puzzle(GeneratorConfig {
-                timeout: None,
                 target_clues_num: 28,
+                ..Default::default()
             })
         })
     });
     c.bench_function("gen 23", |b| {
         b.iter(|| {
             generate_puzzle(GeneratorConfig {
-                timeout: None,
                 target_clues_num: 23,
+                ..Default::default()
             })
         })
     });
     c.bench_function("gen 22", |b| {
         b.iter(|| {
             generate_puzzle(GeneratorConfig {
-                timeout: None,
                 target_clues_num: 22,
+                ..Default::default()
             })
         })
     });
     c.bench_function("gen 21", |b| {
         b.iter(|| {
             generate_puzzle(GeneratorConfig {
-                timeout: None,
                 target_clues_num: 21,
+                ..Default::default()
             })
         })
     });
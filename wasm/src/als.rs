@@ -0,0 +1,547 @@
+use std::collections::HashSet;
+
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// An almost locked set (ALS) is n uncolored nodes in a house holding n + 1
+// colors in total. If any of its colors is eliminated, the nodes become a
+// locked set, so each of the other colors must be the answer of one of them.
+//
+// A color x is a restricted common color of two ALSs A and B which do not
+// overlap, if all the nodes of A and B holding x see each other. Then x is the
+// answer in at most one of them, so at least one of A and B is locked without
+// x.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Als {
+    pub house: usize,
+    pub nodes: NodeBits,
+    // Bit mask of the colors in the format of ColorBits::bits.
+    pub colors: u16,
+    // For each color, the nodes of the ALS holding it, and the nodes seeing
+    // all of them.
+    color_nodes: [NodeBits; COLOR_COUNT],
+    color_peers: [NodeBits; COLOR_COUNT],
+}
+
+impl Als {
+    fn new(node_arr: &NodeArray, house: usize, nodes: NodeBits, colors: u16) -> Self {
+        let mut color_nodes = [NodeBits::new(); COLOR_COUNT];
+        let mut color_peers = [NodeBits::new(); COLOR_COUNT];
+        for color in mask_to_colors(colors) {
+            let i = color as usize - 1;
+            for idx in nodes.iter() {
+                if node_arr[idx].available_colors.has(color) {
+                    color_nodes[i].set(idx);
+                }
+            }
+            let mut iter = color_nodes[i].iter();
+            let first = NEIGHBOR_BITS_MAP[iter.next().unwrap()];
+            color_peers[i] = iter.fold(first, |acc, idx| acc.intersect(&NEIGHBOR_BITS_MAP[idx]));
+        }
+        Als {
+            house,
+            nodes,
+            colors,
+            color_nodes,
+            color_peers,
+        }
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = ColorType> {
+        mask_to_colors(self.colors)
+    }
+
+    pub fn has_color(&self, color: ColorType) -> bool {
+        self.colors & (1 << (color - 1)) != 0
+    }
+
+    // Returns the nodes of the ALS which have the color available.
+    pub fn nodes_with_color(&self, color: ColorType) -> NodeBits {
+        self.color_nodes[color as usize - 1]
+    }
+
+    // Returns the nodes seeing all the nodes of the ALS holding the color.
+    pub fn peers_of_color(&self, color: ColorType) -> NodeBits {
+        self.color_peers[color as usize - 1]
+    }
+
+    // Returns the restricted common colors with the other ALS as a bit mask.
+    pub fn restricted_common_colors(&self, other: &Als) -> u16 {
+        if !self.nodes.intersect(&other.nodes).is_empty() {
+            return 0;
+        }
+        mask_to_colors(self.colors & other.colors)
+            .filter(|&x| {
+                self.nodes_with_color(x)
+                    .minus(&other.peers_of_color(x))
+                    .is_empty()
+            })
+            .fold(0, |acc, x| acc | (1 << (x - 1)))
+    }
+}
+
+struct AlsSearch<'a> {
+    node_arr: &'a NodeArray,
+    max_size: usize,
+    house: usize,
+    // Uncolored nodes of the house which can be in an ALS.
+    idx_arr: Vec<NodeIndexType>,
+    // The nodes in the intersection of two houses are found twice.
+    found_nodes: HashSet<NodeBits>,
+    found: Vec<Als>,
+}
+
+impl AlsSearch<'_> {
+    fn search(&mut self, start: usize, nodes: NodeBits, colors: u16) {
+        let size = nodes.count();
+        if size > 0 && colors.count_ones() as usize == size + 1 && self.found_nodes.insert(nodes) {
+            self.found
+                .push(Als::new(self.node_arr, self.house, nodes, colors));
+        }
+        if size == self.max_size {
+            return;
+        }
+        for i in start..self.idx_arr.len() {
+            let idx = self.idx_arr[i];
+            let union = colors | self.node_arr[idx].available_colors.bits();
+            if union.count_ones() as usize > self.max_size + 1 {
+                continue;
+            }
+            let mut next = nodes;
+            next.set(idx);
+            self.search(i + 1, next, union);
+        }
+    }
+}
+
+// Returns the ALSs with at most max_size nodes, including the bivalue nodes
+// as the ALSs of size 1.
+pub fn find_als(node_arr: &NodeArray, max_size: usize) -> Vec<Als> {
+    let mut search = AlsSearch {
+        node_arr,
+        max_size,
+        house: 0,
+        idx_arr: Vec::new(),
+        found_nodes: HashSet::new(),
+        found: Vec::new(),
+    };
+    for house in 0..HOUSE_COUNT {
+        search.house = house;
+        search.idx_arr = get_all_idx_for_house(house)
+            .into_iter()
+            .filter(|&idx| {
+                let node = &node_arr[idx];
+                node.color == 0 && node.available_colors.count() <= max_size + 1
+            })
+            .collect();
+        search.search(0, NodeBits::new(), 0);
+    }
+    search.found
+}
+
+// Returns the candidates of color seeing all the nodes of the ALSs holding it.
+fn common_peers(node_arr: &NodeArray, als_arr: &[&Als], color: ColorType) -> Vec<Candidate> {
+    als_arr
+        .iter()
+        .fold(
+            get_nodes_with_available_color(node_arr, color),
+            |acc, als| acc.intersect(&als.peers_of_color(color)),
+        )
+        .iter()
+        .map(|idx| Candidate { idx, color })
+        .collect()
+}
+
+fn als_step(technique: Technique, als_arr: &[&Als], eliminations: Vec<Candidate>) -> Step {
+    let nodes = als_arr
+        .iter()
+        .fold(NodeBits::new(), |acc, als| acc.union(&als.nodes));
+    Step {
+        technique,
+        nodes: nodes.iter().collect(),
+        eliminations,
+    }
+}
+
+// ALSs with the restricted common colors between each pair of them.
+struct AlsGraph {
+    als_arr: Vec<Als>,
+    links: Vec<Vec<(usize, u16)>>,
+}
+
+impl AlsGraph {
+    fn new(node_arr: &NodeArray, max_size: usize) -> Self {
+        let als_arr = find_als(node_arr, max_size);
+        let mut links = vec![Vec::new(); als_arr.len()];
+        for i in 0..als_arr.len() {
+            for j in i + 1..als_arr.len() {
+                let rcc = als_arr[i].restricted_common_colors(&als_arr[j]);
+                if rcc != 0 {
+                    links[i].push((j, rcc));
+                    links[j].push((i, rcc));
+                }
+            }
+        }
+        AlsGraph { als_arr, links }
+    }
+}
+
+// A chain of ALSs A1, A2, ..., An, where each pair of neighbors has a
+// restricted common color x1, x2, ..., x(n-1), and x(i) != x(i+1). If A1 is
+// not locked with a color z, x1 must be in A1, so A2 is locked without x1, and
+// x2 must be in A2, ..., and An is locked without x(n-1). So z can be
+// eliminated from the nodes seeing both A1 and An, if z is a color of both
+// other than x1 and x(n-1).
+struct AlsChainSearch<'a> {
+    node_arr: &'a NodeArray,
+    graph: AlsGraph,
+    min_length: usize,
+    max_length: usize,
+    // The technique of a chain of the given number of ALSs.
+    technique: fn(usize) -> Technique,
+    steps: Vec<Step>,
+}
+
+impl AlsChainSearch<'_> {
+    fn search_all(&mut self) {
+        for i in 0..self.graph.als_arr.len() {
+            let nodes = self.graph.als_arr[i].nodes;
+            self.search(&mut vec![i], nodes, 0, 0);
+        }
+    }
+
+    // Extends the chain, whose nodes are `nodes`, with the last restricted
+    // common color `last_x`.
+    fn search(&mut self, chain: &mut Vec<usize>, nodes: NodeBits, first_x: u16, last_x: u16) {
+        let len = chain.len();
+        // Each chain is found in both directions, so only one is checked.
+        if len >= self.min_length && chain[0] < chain[len - 1] {
+            self.check(chain, first_x, last_x);
+        }
+        if len == self.max_length {
+            return;
+        }
+        let last = chain[len - 1];
+        for k in 0..self.graph.links[last].len() {
+            let (next, rcc) = self.graph.links[last][k];
+            if !self.graph.als_arr[next].nodes.intersect(&nodes).is_empty() {
+                continue;
+            }
+            for x in mask_to_colors(rcc & !last_x) {
+                let x = 1 << (x - 1);
+                chain.push(next);
+                let next_nodes = nodes.union(&self.graph.als_arr[next].nodes);
+                self.search(chain, next_nodes, if len == 1 { x } else { first_x }, x);
+                chain.pop();
+            }
+        }
+    }
+
+    fn check(&mut self, chain: &[usize], first_x: u16, last_x: u16) {
+        let als_arr = &self.graph.als_arr;
+        let ends = [&als_arr[chain[0]], &als_arr[chain[chain.len() - 1]]];
+        let mut eliminations = Vec::new();
+        for z in mask_to_colors(ends[0].colors & ends[1].colors & !first_x & !last_x) {
+            eliminations.append(&mut common_peers(self.node_arr, &ends, z));
+        }
+        if eliminations.is_empty() {
+            return;
+        }
+        let als_arr = chain.iter().map(|&i| &als_arr[i]).collect::<Vec<_>>();
+        self.steps.push(als_step(
+            (self.technique)(chain.len()),
+            &als_arr,
+            eliminations,
+        ));
+    }
+}
+
+// Two ALSs A and B with a restricted common color x. Either A or B is locked
+// without x, so a common color z can be eliminated from the nodes seeing all
+// the nodes of A and B holding z.
+//
+// If A and B have two restricted common colors, each of them must be in
+// exactly one of A and B, so both A and B are locked with the other colors.
+pub struct AlsXzEliminator {
+    pub max_size: usize,
+}
+
+impl StrategySolver for AlsXzEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let graph = AlsGraph::new(node_arr, self.max_size);
+        let mut steps = Vec::new();
+        for (i, a) in graph.als_arr.iter().enumerate() {
+            for &(j, rcc) in graph.links[i].iter() {
+                if j < i {
+                    continue;
+                }
+                let b = &graph.als_arr[j];
+                let mut eliminations = Vec::new();
+                let doubly_linked = rcc.count_ones() >= 2;
+                if doubly_linked {
+                    for x in mask_to_colors(rcc) {
+                        eliminations.append(&mut common_peers(node_arr, &[a, b], x));
+                    }
+                    for als in [a, b] {
+                        for z in mask_to_colors(als.colors & !rcc) {
+                            eliminations.append(&mut common_peers(node_arr, &[als], z));
+                        }
+                    }
+                } else {
+                    for z in mask_to_colors(a.colors & b.colors & !rcc) {
+                        eliminations.append(&mut common_peers(node_arr, &[a, b], z));
+                    }
+                }
+                if !eliminations.is_empty() {
+                    eliminations.sort();
+                    eliminations.dedup();
+                    steps.push(als_step(
+                        Technique::AlsXz { doubly_linked },
+                        &[a, b],
+                        eliminations,
+                    ));
+                }
+            }
+        }
+        steps
+    }
+}
+
+// ALSs A and B, each with a restricted common color with a pivot ALS C, x and
+// y respectively where x != y. This is an ALS chain of A, C and B.
+pub struct AlsXyWingEliminator {
+    pub max_size: usize,
+}
+
+impl StrategySolver for AlsXyWingEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut search = AlsChainSearch {
+            node_arr,
+            graph: AlsGraph::new(node_arr, self.max_size),
+            min_length: 3,
+            max_length: 3,
+            technique: |_| Technique::AlsXyWing,
+            steps: Vec::new(),
+        };
+        search.search_all();
+        search.steps
+    }
+}
+
+// ALS chains of four or more ALSs.
+pub struct AlsChainEliminator {
+    pub max_size: usize,
+    // Max number of ALSs in a chain.
+    pub max_length: usize,
+}
+
+impl StrategySolver for AlsChainEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut search = AlsChainSearch {
+            node_arr,
+            graph: AlsGraph::new(node_arr, self.max_size),
+            min_length: 4,
+            max_length: self.max_length,
+            technique: |length| Technique::AlsChain { length },
+            steps: Vec::new(),
+        };
+        search.search_all();
+        search.steps
+    }
+}
+
+// A stem node with colors d1, d2, ..., dk, and a petal ALS for each color di,
+// whose nodes holding di all see the stem. Whichever color the stem is, the
+// petal of the color is locked without it. So a color z of all the petals
+// which is not in the stem can be eliminated from the nodes seeing all the
+// nodes of the petals holding z.
+pub struct DeathBlossomEliminator {
+    pub max_size: usize,
+}
+
+struct BlossomSearch<'a> {
+    stem: NodeIndexType,
+    colors: Vec<ColorType>,
+    // Petals for each color of the stem.
+    petals: Vec<Vec<&'a Als>>,
+    steps: Vec<Step>,
+}
+
+impl<'a> BlossomSearch<'a> {
+    // Picks a petal for colors[k], where `targets` are the nodes seeing the
+    // picked petals for each z.
+    fn search(
+        &mut self,
+        k: usize,
+        picked: &mut Vec<&'a Als>,
+        nodes: NodeBits,
+        targets: [NodeBits; COLOR_COUNT],
+    ) {
+        if targets.iter().all(|t| t.is_empty()) {
+            return;
+        }
+        if k == self.colors.len() {
+            let mut eliminations = Vec::new();
+            for (i, t) in targets.iter().enumerate() {
+                for idx in t.iter() {
+                    eliminations.push(Candidate {
+                        idx,
+                        color: i as ColorType + 1,
+                    });
+                }
+            }
+            let mut step = als_step(Technique::DeathBlossom, picked, eliminations);
+            step.nodes.insert(0, self.stem);
+            self.steps.push(step);
+            return;
+        }
+        for i in 0..self.petals[k].len() {
+            let petal = self.petals[k][i];
+            if !petal.nodes.intersect(&nodes).is_empty() {
+                continue;
+            }
+            let mut next_targets = [NodeBits::new(); COLOR_COUNT];
+            for (z, t) in next_targets.iter_mut().enumerate() {
+                if petal.has_color(z as ColorType + 1) {
+                    *t = targets[z].intersect(&petal.color_peers[z]);
+                }
+            }
+            picked.push(petal);
+            self.search(k + 1, picked, nodes.union(&petal.nodes), next_targets);
+            picked.pop();
+        }
+    }
+}
+
+impl StrategySolver for DeathBlossomEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let als_arr = find_als(node_arr, self.max_size);
+        let mut steps = Vec::new();
+        for (stem, node) in node_arr.iter().enumerate() {
+            if node.color != 0 || node.available_colors.count() < 2 {
+                continue;
+            }
+            let colors = node.available_colors.get_all();
+            let petals = colors
+                .iter()
+                .map(|&d| {
+                    als_arr
+                        .iter()
+                        .filter(|als| {
+                            als.has_color(d)
+                                && !als.nodes.has(stem)
+                                && als.peers_of_color(d).has(stem)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            if petals.iter().any(|p| p.is_empty()) {
+                continue;
+            }
+
+            let mut targets = [NodeBits::new(); COLOR_COUNT];
+            for (z, t) in targets.iter_mut().enumerate() {
+                if !node.available_colors.has(z as ColorType + 1) {
+                    *t = get_nodes_with_available_color(node_arr, z as ColorType + 1);
+                }
+            }
+            let mut search = BlossomSearch {
+                stem,
+                colors,
+                petals,
+                steps: Vec::new(),
+            };
+            search.search(0, &mut Vec::new(), NodeBits::from_idx_arr(&[stem]), targets);
+            steps.append(&mut search.steps);
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn eliminations(steps: &[Step]) -> Vec<Candidate> {
+        let mut ret = steps
+            .iter()
+            .flat_map(|s| s.eliminations.iter().copied())
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    #[test]
+    fn find_als_in_house() {
+        // r0c0 {1, 2} and r0c1 {1, 2, 3} form an ALS of size 2, and r0c0 is an
+        // ALS of size 1. r0c0 and r0c1 are also in square 0.
+        let node_arr = node_arr_with_candidates(&[(0, &[1, 2]), (1, &[1, 2, 3])]);
+        let mut found = find_als(&node_arr, 2)
+            .into_iter()
+            .map(|als| (als.nodes.iter().collect::<Vec<_>>(), als.colors))
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, vec![(vec![0], 0b11), (vec![0, 1], 0b111)]);
+
+        assert_eq!(find_als(&node_arr, 1).len(), 1);
+    }
+
+    #[test]
+    fn restricted_common_colors() {
+        // A: r0c0 {1, 2} and r0c1 {2, 3}, B: r4c1 {3, 4} and r4c5 {1, 4}.
+        let node_arr =
+            node_arr_with_candidates(&[(0, &[1, 2]), (1, &[2, 3]), (37, &[3, 4]), (41, &[1, 4])]);
+        let als_arr = find_als(&node_arr, 2);
+        let find = |nodes: &[NodeIndexType]| {
+            *als_arr
+                .iter()
+                .find(|als| als.nodes == NodeBits::from_idx_arr(nodes))
+                .unwrap()
+        };
+        let (a, b) = (find(&[0, 1]), find(&[37, 41]));
+        assert_eq!(a.restricted_common_colors(&b), 0b100);
+        assert_eq!(a.nodes_with_color(2), NodeBits::from_idx_arr(&[0, 1]));
+
+        let steps = AlsXzEliminator { max_size: 2 }.find_steps(&node_arr);
+        assert_eq!(
+            eliminations(&steps),
+            vec![
+                Candidate { idx: 5, color: 1 },
+                Candidate { idx: 36, color: 1 }
+            ]
+        );
+    }
+
+    #[test]
+    fn als_xy_wing() {
+        // A: r0c0 {1, 2}, C: r0c4 {2, 3}, B: r4c4 {3, 1}.
+        let node_arr = node_arr_with_candidates(&[(0, &[1, 2]), (4, &[2, 3]), (40, &[1, 3])]);
+        let steps = AlsXyWingEliminator { max_size: 1 }.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].nodes, vec![0, 4, 40]);
+        assert_eq!(steps[0].eliminations, vec![Candidate { idx: 36, color: 1 }]);
+    }
+
+    #[test]
+    fn death_blossom() {
+        // Stem r4c4 {1, 2}, with petals r0c4 {1, 5} and r4c0 {2, 5}.
+        let node_arr = node_arr_with_candidates(&[(40, &[1, 2]), (4, &[1, 5]), (36, &[2, 5])]);
+        let steps = DeathBlossomEliminator { max_size: 2 }.find_steps(&node_arr);
+        assert_eq!(eliminations(&steps), vec![Candidate { idx: 0, color: 5 }]);
+        assert_eq!(steps[0].nodes[0], 40);
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer(&AlsXzEliminator { max_size: 4 });
+        check_steps_keep_answer(&AlsXyWingEliminator { max_size: 3 });
+        check_steps_keep_answer(&AlsChainEliminator {
+            max_size: 3,
+            max_length: 5,
+        });
+        check_steps_keep_answer(&DeathBlossomEliminator { max_size: 3 });
+    }
+}
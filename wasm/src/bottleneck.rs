@@ -0,0 +1,121 @@
+use std::cmp::Reverse;
+
+use crate::*;
+use rating::*;
+use scorer::*;
+
+// Bottleneck analysis follows the solve which always uses the easiest steps
+// available, like the SE rating. At each point of the solve, the rating of the
+// easiest technique tells how hard it is to make progress, and the number of
+// candidates it places or eliminates tells how many options the player has.
+// The bottleneck is the point where the easiest technique is the hardest, and
+// the pinch points are where only one candidate can be progressed.
+
+// A point of the solve path.
+#[derive(Clone, Debug)]
+pub struct TimelinePoint {
+    // The easiest technique available, which is used.
+    pub name: &'static str,
+    pub rating: i32,
+    // Number of candidates the easiest steps place or eliminate.
+    pub options: usize,
+    // Candidates left before the steps.
+    pub remaining: i32,
+    // The steps made, empty for the singles and the solvers which cannot
+    // explain their work.
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BottleneckAnalysis {
+    pub timeline: Vec<TimelinePoint>,
+    // Index of the point with the hardest rating in the timeline, the one
+    // with fewer options among equal ratings.
+    pub bottleneck: Option<usize>,
+    // Indexes of the points with a single option in the timeline.
+    pub pinch_points: Vec<usize>,
+    // Whether the techniques are enough to solve the puzzle.
+    pub solved: bool,
+    // The total score by score_with_config.
+    pub score: i32,
+}
+
+pub fn analyze_bottlenecks(puzzle: &ColorArray, config: &ScorerConfig) -> BottleneckAnalysis {
+    let solve = rated_solve(puzzle, config);
+    let mut timeline = Vec::new();
+    for (i, point) in solve.points.iter().enumerate() {
+        let end = solve
+            .points
+            .get(i + 1)
+            .map_or(solve.steps.len(), |p| p.first_step);
+        let rated = &solve.steps[point.first_step..end];
+        timeline.push(TimelinePoint {
+            name: rated[0].name,
+            rating: rated[0].rating,
+            options: point.options,
+            remaining: point.remaining,
+            steps: rated.iter().filter_map(|r| r.step.clone()).collect(),
+        });
+    }
+
+    let bottleneck = timeline
+        .iter()
+        .enumerate()
+        .max_by_key(|&(i, p)| (p.rating, Reverse(p.options), Reverse(i)))
+        .map(|(i, _)| i);
+    let pinch_points = (0..timeline.len())
+        .filter(|&i| timeline[i].options == 1)
+        .collect();
+    BottleneckAnalysis {
+        timeline,
+        bottleneck,
+        pinch_points,
+        solved: solve.solved,
+        score: score_with_config(puzzle, config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn bottleneck_is_hardest_point() {
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        let analysis = analyze_bottlenecks(&puzzle, &ScorerConfig::default());
+        assert!(analysis.solved);
+        let rating = se_rating(&puzzle, &ScorerConfig::default());
+        let bottleneck = &analysis.timeline[analysis.bottleneck.unwrap()];
+        assert_eq!(bottleneck.rating, rating.rating);
+        assert_eq!(bottleneck.name, rating.hardest.unwrap().name);
+        assert!(!bottleneck.steps.is_empty());
+    }
+
+    #[test]
+    fn timeline_makes_progress() {
+        for src in TEST_PUZZLES[..6].iter() {
+            let puzzle = parse_sudoku_array(src).unwrap();
+            let analysis = analyze_bottlenecks(&puzzle, &ScorerConfig::default());
+            for pair in analysis.timeline.windows(2) {
+                assert!(pair[0].options > 0);
+                assert!(pair[1].remaining <= pair[0].remaining);
+            }
+            for &i in analysis.pinch_points.iter() {
+                assert_eq!(analysis.timeline[i].options, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn pinch_point_of_single_option() {
+        // Only the last node of the first row is empty.
+        let mut puzzle = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        puzzle[8] = 0;
+        let analysis = analyze_bottlenecks(&puzzle, &ScorerConfig::default());
+        assert_eq!(analysis.timeline.len(), 1);
+        assert_eq!(analysis.timeline[0].name, "last_digit");
+        assert_eq!(analysis.pinch_points, vec![0]);
+        assert_eq!(analysis.bottleneck, Some(0));
+    }
+}
@@ -0,0 +1,506 @@
+use std::{fmt, path::Path};
+
+use crate::*;
+use pipeline::*;
+use scorer::*;
+
+// Calibration fits the weights of the pipeline stages to the solve times of
+// players. Each record is turned into the number of eliminations of each
+// stage, and the weights are fitted by ridge regression, so that the sum of
+// the weighted eliminations predicts the solve time.
+//
+// The solve times are scaled to the current scores first, and the regression
+// pulls the weights toward the current ones, so that the scores keep their
+// scale, and the stages with little data keep their weights. The puzzles on
+// which the techniques get stuck are skipped, since the times of trial and
+// error cannot be told apart from the times of the techniques.
+
+// A solve by a player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolveRecord {
+    pub puzzle: ColorArray,
+    pub solve_time_secs: f64,
+    pub hints: u32,
+    pub mistakes: u32,
+}
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    Io(std::io::Error),
+    // Line number starting from 1, and what is wrong with it.
+    Parse(usize, String),
+    NoRecords,
+    // A weight of a stage which is not in the pipeline.
+    UnknownStage(String),
+    InvalidRidge(f64),
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalibrationError::Io(err) => write!(f, "{}", err),
+            CalibrationError::Parse(line, message) => write!(f, "line {}: {}", line, message),
+            CalibrationError::NoRecords => write!(f, "no records"),
+            CalibrationError::UnknownStage(name) => write!(f, "unknown stage: {}", name),
+            CalibrationError::InvalidRidge(ridge) => write!(f, "ridge must be positive: {}", ridge),
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {}
+
+fn parse_field<T: std::str::FromStr>(
+    field: Option<&str>,
+    name: &str,
+    line: usize,
+) -> Result<T, CalibrationError> {
+    let field = field.ok_or_else(|| CalibrationError::Parse(line, format!("missing {}", name)))?;
+    field
+        .trim()
+        .parse()
+        .map_err(|_| CalibrationError::Parse(line, format!("invalid {}: {}", name, field)))
+}
+
+// Parses the records from CSV lines of `puzzle,solve_time_secs,hints,mistakes`.
+// An optional header line starting with "puzzle" and empty lines are skipped.
+pub fn parse_records(csv: &str) -> Result<Vec<SolveRecord>, CalibrationError> {
+    let mut ret = Vec::new();
+    for (i, line) in csv.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.trim();
+        if line.is_empty() || (i == 0 && line.starts_with("puzzle")) {
+            continue;
+        }
+        let mut fields = line.split(',');
+        let puzzle = fields.next().unwrap_or_default();
+        let puzzle = parse_sudoku_array(puzzle).ok_or_else(|| {
+            CalibrationError::Parse(line_num, format!("invalid puzzle: {}", puzzle))
+        })?;
+        let solve_time_secs: f64 = parse_field(fields.next(), "solve time", line_num)?;
+        if !solve_time_secs.is_finite() || solve_time_secs < 0.0 {
+            return Err(CalibrationError::Parse(
+                line_num,
+                format!("invalid solve time: {}", solve_time_secs),
+            ));
+        }
+        let record = SolveRecord {
+            puzzle,
+            solve_time_secs,
+            hints: parse_field(fields.next(), "hints", line_num)?,
+            mistakes: parse_field(fields.next(), "mistakes", line_num)?,
+        };
+        if fields.next().is_some() {
+            return Err(CalibrationError::Parse(line_num, "too many fields".into()));
+        }
+        ret.push(record);
+    }
+    Ok(ret)
+}
+
+pub fn load_records(path: &Path) -> Result<Vec<SolveRecord>, CalibrationError> {
+    let csv = std::fs::read_to_string(path).map_err(CalibrationError::Io)?;
+    parse_records(&csv)
+}
+
+// Stage weights which can be loaded by the scorer, see score_with_profile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WeightProfile {
+    pub weights: Vec<(String, i32)>,
+}
+
+impl WeightProfile {
+    // Parses the `name=weight` lines written by Display. The names must be the
+    // names of pipeline stages.
+    pub fn parse(src: &str) -> Result<Self, CalibrationError> {
+        let pipeline = PipelineBuilder::preset(PipelinePreset::Complete, &[0; NODE_COUNT]).build();
+        let mut weights = Vec::new();
+        for (i, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((name, weight)) = line.split_once('=') else {
+                return Err(CalibrationError::Parse(
+                    i + 1,
+                    "expected name=weight".into(),
+                ));
+            };
+            let name = name.trim();
+            if !pipeline.stages().iter().any(|s| s.name == name) {
+                return Err(CalibrationError::Parse(
+                    i + 1,
+                    format!("unknown stage: {}", name),
+                ));
+            }
+            let weight = parse_field(Some(weight), "weight", i + 1)?;
+            weights.push((name.to_string(), weight));
+        }
+        Ok(Self { weights })
+    }
+
+    // Sets the weights of the stages in the profile, which must all be in the
+    // pipeline.
+    pub fn apply(&self, mut builder: PipelineBuilder) -> Result<PipelineBuilder, CalibrationError> {
+        for (name, weight) in self.weights.iter() {
+            if !builder.has_stage(name) {
+                return Err(CalibrationError::UnknownStage(name.clone()));
+            }
+            builder = builder.weight(name, *weight);
+        }
+        Ok(builder)
+    }
+}
+
+impl fmt::Display for WeightProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, weight) in self.weights.iter() {
+            writeln!(f, "{}={}", name, weight)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CalibrationConfig {
+    // Seconds added to the solve time for each hint and each mistake, since
+    // the player would have been slower without them.
+    pub hint_penalty_secs: f64,
+    pub mistake_penalty_secs: f64,
+    // How strongly the weights are pulled toward the current ones. Must be
+    // positive.
+    pub ridge: f64,
+    pub scorer: ScorerConfig,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            hint_penalty_secs: 30.0,
+            mistake_penalty_secs: 10.0,
+            ridge: 1.0,
+            scorer: ScorerConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Calibration {
+    pub profile: WeightProfile,
+    // Coefficient of determination of the fitted weights on the scaled solve
+    // times, before rounding.
+    pub r_squared: f64,
+    // Number of records skipped since their puzzles need backtracking.
+    pub skipped: usize,
+}
+
+// Solves the linear system by Gaussian elimination with partial pivoting. The
+// matrix is positive definite here since the ridge is positive, so it is
+// never singular.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let (upper, lower) = a.split_at_mut(row);
+            let factor = lower[0][col] / upper[col][col];
+            for (x, p) in lower[0][col..].iter_mut().zip(upper[col][col..].iter()) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum = (row + 1..n).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x
+}
+
+pub fn calibrate(
+    records: &[SolveRecord],
+    config: &CalibrationConfig,
+) -> Result<Calibration, CalibrationError> {
+    if !config.ridge.is_finite() || config.ridge <= 0.0 {
+        return Err(CalibrationError::InvalidRidge(config.ridge));
+    }
+    if records.is_empty() {
+        return Err(CalibrationError::NoRecords);
+    }
+
+    // The stages are the same for all the puzzles, except the givens of
+    // avoidable rectangles, which do not change the names and weights.
+    let stages = PipelineBuilder::from_config(&records[0].puzzle, &config.scorer).build();
+    let features = stages
+        .stages()
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.enabled)
+        .map(|(i, s)| (i, s.name, s.weight as f64))
+        .collect::<Vec<_>>();
+    let n = features.len();
+
+    let mut xs = Vec::new();
+    let mut times = Vec::new();
+    let mut skipped = 0;
+    for record in records {
+        let pipeline = PipelineBuilder::from_config(&record.puzzle, &config.scorer).build();
+        let mut node_arr = NodeArray::from_color_array(&record.puzzle);
+        let counts = pipeline.run_counting(&mut node_arr);
+        if !node_arr.validate_colors(true) {
+            skipped += 1;
+            continue;
+        }
+        xs.push(
+            features
+                .iter()
+                .map(|&(i, _, _)| counts[i] as f64)
+                .collect::<Vec<_>>(),
+        );
+        times.push(
+            record.solve_time_secs
+                + config.hint_penalty_secs * record.hints as f64
+                + config.mistake_penalty_secs * record.mistakes as f64,
+        );
+    }
+
+    if xs.is_empty() {
+        return Err(CalibrationError::NoRecords);
+    }
+
+    // Scale the times to the current scores.
+    let current = xs
+        .iter()
+        .map(|x| {
+            x.iter()
+                .zip(features.iter())
+                .map(|(c, f)| c * f.2)
+                .sum::<f64>()
+        })
+        .collect::<Vec<_>>();
+    let time_sum = times.iter().sum::<f64>();
+    let scale = if time_sum > 0.0 {
+        current.iter().sum::<f64>() / time_sum
+    } else {
+        1.0
+    };
+    let ys = times.iter().map(|t| t * scale).collect::<Vec<_>>();
+
+    // (X^T X + ridge * I) w = X^T y + ridge * w0
+    let mut a = vec![vec![0.0; n]; n];
+    let mut b = features
+        .iter()
+        .map(|f| config.ridge * f.2)
+        .collect::<Vec<_>>();
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        for i in 0..n {
+            b[i] += x[i] * y;
+            for j in 0..n {
+                a[i][j] += x[i] * x[j];
+            }
+        }
+    }
+    for (i, row) in a.iter_mut().enumerate() {
+        row[i] += config.ridge;
+    }
+    let weights = solve_linear(a, b)
+        .into_iter()
+        .map(|w| w.max(0.0))
+        .collect::<Vec<_>>();
+
+    let mean = ys.iter().sum::<f64>() / ys.len() as f64;
+    let mut residual = 0.0;
+    let mut total = 0.0;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        let predicted = x
+            .iter()
+            .zip(weights.iter())
+            .map(|(c, w)| c * w)
+            .sum::<f64>();
+        residual += (y - predicted).powi(2);
+        total += (y - mean).powi(2);
+    }
+    let r_squared = if total > 0.0 {
+        1.0 - residual / total
+    } else {
+        1.0
+    };
+
+    let weights = features
+        .iter()
+        .zip(weights)
+        .map(|(f, w)| (f.1.to_string(), w.round() as i32))
+        .collect();
+    Ok(Calibration {
+        profile: WeightProfile { weights },
+        r_squared,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn parse_records() {
+        let csv = format!(
+            "puzzle,solve_time_secs,hints,mistakes\n{},120.5,1,2\n\n{},60,0,0\n",
+            TEST_PUZZLES[0], TEST_PUZZLES[1]
+        );
+        let records = super::parse_records(&csv).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].solve_time_secs, 120.5);
+        assert_eq!(records[0].hints, 1);
+        assert_eq!(records[0].mistakes, 2);
+        assert_eq!(
+            records[1].puzzle,
+            parse_sudoku_array(TEST_PUZZLES[1]).unwrap()
+        );
+
+        let bad = format!("{},fast,0,0\n", TEST_PUZZLES[0]);
+        match super::parse_records(&bad) {
+            Err(CalibrationError::Parse(line, _)) => assert_eq!(line, 1),
+            other => panic!("{:?}", other),
+        }
+        assert!(super::parse_records("123,1,0,0").is_err());
+        for time in ["NaN", "inf", "-1"] {
+            let bad = format!("{},{},0,0\n", TEST_PUZZLES[0], time);
+            assert!(matches!(
+                super::parse_records(&bad),
+                Err(CalibrationError::Parse(1, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn profile_round_trip() {
+        let profile = WeightProfile {
+            weights: vec![("x_wing".into(), 12), ("medusa".into(), 45)],
+        };
+        assert_eq!(WeightProfile::parse(&profile.to_string()).unwrap(), profile);
+        assert!(WeightProfile::parse("x_wing").is_err());
+        assert!(matches!(
+            WeightProfile::parse("x_wing=16\nx_wings=16"),
+            Err(CalibrationError::Parse(2, _))
+        ));
+    }
+
+    #[test]
+    fn calibrate_to_current_scores() {
+        // Solve times proportional to the current raw scores should keep the
+        // current weights.
+        let records = TEST_PUZZLES[..6]
+            .iter()
+            .map(|src| {
+                let puzzle = parse_sudoku_array(src).unwrap();
+                let breakdown = score_breakdown(&puzzle, &ScorerConfig::default());
+                SolveRecord {
+                    puzzle,
+                    solve_time_secs: breakdown.raw_score as f64 / 10.0,
+                    hints: 0,
+                    mistakes: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        let calibration = calibrate(&records, &CalibrationConfig::default()).unwrap();
+        assert!(calibration.r_squared > 0.99);
+
+        let builder = PipelineBuilder::from_config(&records[0].puzzle, &ScorerConfig::default());
+        let pipeline = builder.build();
+        for (name, weight) in calibration.profile.weights.iter() {
+            let stage = pipeline.stages().iter().find(|s| s.name == name).unwrap();
+            assert!((stage.weight - weight).abs() <= 1, "{}", name);
+        }
+    }
+
+    #[test]
+    fn calibrate_slow_technique() {
+        // Puzzles needing the hardest technique of the first puzzle are solved
+        // much slower than predicted.
+        let puzzles = TEST_PUZZLES[..6]
+            .iter()
+            .map(|src| parse_sudoku_array(src).unwrap())
+            .collect::<Vec<_>>();
+        let config = CalibrationConfig::default();
+        let records = puzzles
+            .iter()
+            .map(|puzzle| {
+                let breakdown = score_breakdown(puzzle, &config.scorer);
+                let slow = breakdown
+                    .techniques
+                    .iter()
+                    .find(|t| t.name == "wxyz_wing")
+                    .unwrap();
+                SolveRecord {
+                    puzzle: *puzzle,
+                    solve_time_secs: (breakdown.raw_score + 10 * slow.score) as f64,
+                    hints: 0,
+                    mistakes: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        let calibration = calibrate(&records, &config).unwrap();
+        let weight = |name| {
+            calibration
+                .profile
+                .weights
+                .iter()
+                .find(|w| w.0 == name)
+                .unwrap()
+                .1
+        };
+        assert!(weight("wxyz_wing") > 36);
+    }
+
+    #[test]
+    fn no_records() {
+        assert!(matches!(
+            calibrate(&[], &CalibrationConfig::default()),
+            Err(CalibrationError::NoRecords)
+        ));
+    }
+
+    #[test]
+    fn skip_backtracking() {
+        let record = |src| SolveRecord {
+            puzzle: parse_sudoku_array(src).unwrap(),
+            solve_time_secs: 600.0,
+            hints: 0,
+            mistakes: 0,
+        };
+        // The 8th test puzzle needs backtracking.
+        let config = CalibrationConfig::default();
+        let records = [record(TEST_PUZZLES[0]), record(TEST_PUZZLES[7])];
+        assert_eq!(calibrate(&records, &config).unwrap().skipped, 1);
+        assert!(matches!(
+            calibrate(&records[1..], &config),
+            Err(CalibrationError::NoRecords)
+        ));
+    }
+
+    #[test]
+    fn invalid_ridge() {
+        let records = [SolveRecord {
+            puzzle: parse_sudoku_array(TEST_PUZZLES[0]).unwrap(),
+            solve_time_secs: 600.0,
+            hints: 0,
+            mistakes: 0,
+        }];
+        for ridge in [0.0, -1.0, f64::NAN] {
+            let config = CalibrationConfig {
+                ridge,
+                ..Default::default()
+            };
+            assert!(matches!(
+                calibrate(&records, &config),
+                Err(CalibrationError::InvalidRidge(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::*;
+use als::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// An alternating inference chain (AIC) is a chain of nodes connected by
+// alternating strong and weak links, which starts and ends with strong links.
+// A strong link means at least one of the two nodes is true, and a weak link
+// means at most one of them is true. If the first node is false, the second
+// one is true, the third one is false, ..., and the last one is true, so at
+// least one of the two ends is true. The candidates which cannot be true
+// together with either end are eliminated.
+//
+// A chain node is true if its color is the answer of any of its nodes. It is
+// either a single candidate, a group of candidates in the intersection of a
+// square and a line, or the candidates of a color in an ALS. If an ALS loses a
+// color, each of its other colors must be the answer of one of its nodes, so
+// the chain nodes of any two colors of an ALS are strongly linked.
+//
+// If the last node is also weakly linked to the first one, the chain is a
+// continuous nice loop, where exactly one node of each link is true. Then the
+// candidates which cannot be true together with either node of any link are
+// eliminated.
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ChainNodeKind {
+    Candidate,
+    Group,
+    Als,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChainNode {
+    pub kind: ChainNodeKind,
+    pub color: ColorType,
+    pub nodes: NodeBits,
+}
+
+// The links which can be used in a chain.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChainKind {
+    // Links of a single color between candidates.
+    X,
+    // Strong links inside bivalue nodes, and weak links of a single color
+    // between the candidates of bivalue nodes.
+    Xy,
+    // Any links between candidates, with grouped and ALS nodes optionally.
+    Aic { grouped: bool, als: bool },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chain {
+    pub technique: Technique,
+    // The links alternate as nodes[0] = nodes[1] - nodes[2] = ... = nodes[n - 1].
+    // In a continuous nice loop, nodes[n - 1] is also weakly linked to
+    // nodes[0].
+    pub nodes: Vec<ChainNode>,
+    pub eliminations: Vec<Candidate>,
+}
+
+impl Chain {
+    pub fn to_step(&self) -> Step {
+        let mut nodes = Vec::new();
+        for node in self.nodes.iter() {
+            for idx in node.nodes.iter() {
+                if !nodes.contains(&idx) {
+                    nodes.push(idx);
+                }
+            }
+        }
+        Step {
+            technique: self.technique,
+            nodes,
+            eliminations: self.eliminations.clone(),
+        }
+    }
+}
+
+// ALS nodes are the most expensive part of the search, so large ALSs are not
+// used.
+const MAX_ALS_SIZE: usize = 4;
+
+// Candidates grouped by colors, i.e. candidates[color - 1] holds the nodes.
+type CandidateBits = [NodeBits; COLOR_COUNT];
+
+fn intersect_candidates(a: &CandidateBits, b: &CandidateBits) -> CandidateBits {
+    let mut ret = *a;
+    for (r, b) in ret.iter_mut().zip(b.iter()) {
+        *r = r.intersect(b);
+    }
+    ret
+}
+
+fn union_candidates(a: &CandidateBits, b: &CandidateBits) -> CandidateBits {
+    let mut ret = *a;
+    for (r, b) in ret.iter_mut().zip(b.iter()) {
+        *r = r.union(b);
+    }
+    ret
+}
+
+// Returns the nodes seeing all the given nodes.
+fn common_neighbors(nodes: &NodeBits) -> NodeBits {
+    let mut iter = nodes.iter();
+    let first = NEIGHBOR_BITS_MAP[iter.next().unwrap()];
+    iter.fold(first, |acc, idx| acc.intersect(&NEIGHBOR_BITS_MAP[idx]))
+}
+
+struct ChainGraph {
+    nodes: Vec<ChainNode>,
+    strong_links: Vec<Vec<usize>>,
+    weak_links: Vec<Vec<usize>>,
+    // The candidates which cannot be true together with each node.
+    seen: Vec<CandidateBits>,
+}
+
+impl ChainGraph {
+    fn new(node_arr: &NodeArray, kind: ChainKind) -> Self {
+        let mut candidates = [NodeBits::new(); COLOR_COUNT];
+        for (i, c) in candidates.iter_mut().enumerate() {
+            *c = get_nodes_with_available_color(node_arr, i as ColorType + 1);
+        }
+        let is_bivalue = |idx: NodeIndexType| {
+            node_arr[idx].color == 0 && node_arr[idx].available_colors.count() == 2
+        };
+        let (grouped, als) = match kind {
+            ChainKind::Aic { grouped, als } => (grouped, als),
+            _ => (false, false),
+        };
+
+        let mut graph = ChainGraph {
+            nodes: Vec::new(),
+            strong_links: Vec::new(),
+            weak_links: Vec::new(),
+            seen: Vec::new(),
+        };
+        for color in 1..=COLOR_COUNT as ColorType {
+            for idx in candidates[color as usize - 1].iter() {
+                if kind == ChainKind::Xy && !is_bivalue(idx) {
+                    continue;
+                }
+                graph.nodes.push(ChainNode {
+                    kind: ChainNodeKind::Candidate,
+                    color,
+                    nodes: NodeBits::from_idx_arr(&[idx]),
+                });
+            }
+        }
+        if grouped {
+            graph.add_groups(&candidates);
+        }
+        let als_nodes = if als {
+            graph.add_als_nodes(node_arr)
+        } else {
+            Vec::new()
+        };
+
+        let n = graph.nodes.len();
+        graph.strong_links = vec![Vec::new(); n];
+        graph.weak_links = vec![Vec::new(); n];
+        let node_ids = (0..n)
+            .filter(|&i| graph.nodes[i].kind != ChainNodeKind::Als)
+            .map(|i| ((graph.nodes[i].color, graph.nodes[i].nodes), i))
+            .collect::<HashMap<_, _>>();
+
+        // Strong links in houses, where two nodes hold all the candidates of
+        // a color.
+        if kind != ChainKind::Xy {
+            for i in 0..n {
+                let node = graph.nodes[i];
+                if node.kind == ChainNodeKind::Als {
+                    continue;
+                }
+                for house in HOUSE_BITS_ARR.iter() {
+                    let house_candidates = house.intersect(&candidates[node.color as usize - 1]);
+                    if !node.nodes.minus(&house_candidates).is_empty() {
+                        continue;
+                    }
+                    let rest = house_candidates.minus(&node.nodes);
+                    if let Some(&j) = node_ids.get(&(node.color, rest)) {
+                        if i < j {
+                            graph.add_link(true, i, j);
+                        }
+                    }
+                }
+            }
+        }
+        // Strong links in bivalue nodes.
+        if kind != ChainKind::X {
+            for idx in (0..NODE_COUNT).filter(|&idx| is_bivalue(idx)) {
+                let colors = node_arr[idx].available_colors.get_all();
+                let bits = NodeBits::from_idx_arr(&[idx]);
+                graph.add_link(
+                    true,
+                    node_ids[&(colors[0], bits)],
+                    node_ids[&(colors[1], bits)],
+                );
+            }
+        }
+        // Strong links in ALSs.
+        for ids in als_nodes.iter() {
+            for (k, &i) in ids.iter().enumerate() {
+                for &j in ids[k + 1..].iter() {
+                    graph.add_link(true, i, j);
+                }
+            }
+        }
+
+        // Weak links of a single color.
+        let sees = (0..n)
+            .map(|i| common_neighbors(&graph.nodes[i].nodes))
+            .collect::<Vec<_>>();
+        let mut ids_by_color = vec![Vec::new(); COLOR_COUNT];
+        for (i, node) in graph.nodes.iter().enumerate() {
+            ids_by_color[node.color as usize - 1].push(i);
+        }
+        for ids in ids_by_color.iter() {
+            for (k, &i) in ids.iter().enumerate() {
+                for &j in ids[k + 1..].iter() {
+                    // Each pair is visited once, so there is no need to check
+                    // for duplicated links.
+                    if graph.nodes[j].nodes.minus(&sees[i]).is_empty() {
+                        graph.weak_links[i].push(j);
+                        graph.weak_links[j].push(i);
+                    }
+                }
+            }
+        }
+        // Weak links inside nodes.
+        if let ChainKind::Aic { .. } = kind {
+            for (idx, node) in node_arr.iter().enumerate() {
+                if node.color != 0 {
+                    continue;
+                }
+                let bits = NodeBits::from_idx_arr(&[idx]);
+                let colors = node.available_colors.get_all();
+                for (k, &a) in colors.iter().enumerate() {
+                    for &b in colors[k + 1..].iter() {
+                        graph.add_link(false, node_ids[&(a, bits)], node_ids[&(b, bits)]);
+                    }
+                }
+            }
+        }
+
+        graph.seen = (0..n)
+            .map(|i| {
+                let node = &graph.nodes[i];
+                let mut seen = [NodeBits::new(); COLOR_COUNT];
+                seen[node.color as usize - 1] =
+                    sees[i].intersect(&candidates[node.color as usize - 1]);
+                if node.kind == ChainNodeKind::Candidate {
+                    let idx = node.nodes.first().unwrap();
+                    for c in node_arr[idx].available_colors.get_all() {
+                        if c != node.color {
+                            seen[c as usize - 1].set(idx);
+                        }
+                    }
+                }
+                seen
+            })
+            .collect();
+        graph
+    }
+
+    // Adds the groups of at least two candidates in the intersection of a
+    // square and a line.
+    fn add_groups(&mut self, candidates: &CandidateBits) {
+        for color in 1..=COLOR_COUNT as ColorType {
+            for sqr in 0..COLOR_COUNT {
+                let sqr_candidates = candidates[color as usize - 1]
+                    .intersect(&HOUSE_BITS_ARR[2 * COLOR_COUNT + sqr]);
+                let rows = (0..3).map(|i| sqr / 3 * 3 + i);
+                let cols = (0..3).map(|i| COLOR_COUNT + sqr % 3 * 3 + i);
+                for line in rows.chain(cols) {
+                    let nodes = sqr_candidates.intersect(&HOUSE_BITS_ARR[line]);
+                    if nodes.count() >= 2 {
+                        self.nodes.push(ChainNode {
+                            kind: ChainNodeKind::Group,
+                            color,
+                            nodes,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Adds the nodes of the ALSs with at least two nodes, since the bivalue
+    // nodes are covered by the candidates. Returns the ids of the nodes of
+    // each ALS.
+    fn add_als_nodes(&mut self, node_arr: &NodeArray) -> Vec<Vec<usize>> {
+        let mut ret = Vec::new();
+        for als in find_als(node_arr, MAX_ALS_SIZE) {
+            if als.nodes.count() < 2 {
+                continue;
+            }
+            let mut ids = Vec::new();
+            for color in als.colors() {
+                ids.push(self.nodes.len());
+                self.nodes.push(ChainNode {
+                    kind: ChainNodeKind::Als,
+                    color,
+                    nodes: als.nodes_with_color(color),
+                });
+            }
+            ret.push(ids);
+        }
+        ret
+    }
+
+    fn add_link(&mut self, strong: bool, i: usize, j: usize) {
+        let links = if strong {
+            &mut self.strong_links
+        } else {
+            &mut self.weak_links
+        };
+        if !links[i].contains(&j) {
+            links[i].push(j);
+            links[j].push(i);
+        }
+    }
+
+    // Breadth first search of the chains from the start node, so the shortest
+    // chain to each end is found. A search state is a node id * 2, plus 1 if
+    // the node is true.
+    fn search(&self, start: usize, max_length: usize, chains: &mut Vec<Chain>) {
+        let mut parent = vec![usize::MAX; self.nodes.len() * 2];
+        parent[start * 2] = start * 2;
+        let mut layer = vec![start * 2];
+        for _ in 1..max_length {
+            let mut next_layer = Vec::new();
+            for &state in layer.iter() {
+                let (id, is_true) = (state / 2, state % 2 == 1);
+                let links = if is_true {
+                    &self.weak_links[id]
+                } else {
+                    &self.strong_links[id]
+                };
+                for &next in links.iter() {
+                    let next_state = next * 2 + !is_true as usize;
+                    if parent[next_state] == usize::MAX {
+                        parent[next_state] = state;
+                        next_layer.push(next_state);
+                    }
+                }
+            }
+            for &state in next_layer.iter() {
+                if state % 2 == 1 {
+                    self.check_chain(start, state, &parent, chains);
+                }
+            }
+            layer = next_layer;
+        }
+    }
+
+    fn check_chain(
+        &self,
+        start: usize,
+        end_state: usize,
+        parent: &[usize],
+        chains: &mut Vec<Chain>,
+    ) {
+        let end = end_state / 2;
+        let is_loop = end != start && self.weak_links[end].contains(&start);
+        let mut eliminated = intersect_candidates(&self.seen[start], &self.seen[end]);
+        if !is_loop && eliminated.iter().all(|n| n.is_empty()) {
+            return;
+        }
+
+        let mut ids = vec![end];
+        let mut state = end_state;
+        while parent[state] != state {
+            state = parent[state];
+            ids.push(state / 2);
+        }
+        ids.reverse();
+        // A node may be reached both as true and as false, but only the start
+        // node may appear twice, at both ends.
+        let inner = if end == start {
+            &ids[..ids.len() - 1]
+        } else {
+            &ids[..]
+        };
+        if (0..inner.len()).any(|k| inner[k + 1..].contains(&inner[k])) {
+            return;
+        }
+
+        let is_loop = is_loop && ids.len() >= 4;
+        if is_loop {
+            for k in 0..ids.len() {
+                let (a, b) = (ids[k], ids[(k + 1) % ids.len()]);
+                let seen = intersect_candidates(&self.seen[a], &self.seen[b]);
+                eliminated = union_candidates(&eliminated, &seen);
+            }
+        }
+        let mut eliminations = Vec::new();
+        for (i, nodes) in eliminated.iter().enumerate() {
+            for idx in nodes.iter() {
+                eliminations.push(Candidate {
+                    idx,
+                    color: i as ColorType + 1,
+                });
+            }
+        }
+        if eliminations.is_empty() {
+            return;
+        }
+        eliminations.sort();
+
+        let nodes = ids.iter().map(|&id| self.nodes[id]).collect::<Vec<_>>();
+        chains.push(Chain {
+            technique: classify_chain(&nodes, is_loop),
+            nodes,
+            eliminations,
+        });
+    }
+}
+
+fn classify_chain(nodes: &[ChainNode], is_loop: bool) -> Technique {
+    let length = nodes.len();
+    let has_kind = |kind| nodes.iter().any(|n| n.kind == kind);
+    if !has_kind(ChainNodeKind::Group) && !has_kind(ChainNodeKind::Als) {
+        if nodes.iter().all(|n| n.color == nodes[0].color) {
+            return Technique::XChain { length };
+        }
+        let is_xy_chain = (0..nodes.len() - 1).all(|k| {
+            let (a, b) = (&nodes[k], &nodes[k + 1]);
+            if k % 2 == 0 {
+                a.nodes == b.nodes
+            } else {
+                a.color == b.color
+            }
+        });
+        if is_xy_chain {
+            return Technique::XyChain { length };
+        }
+    }
+    let (first, last) = (&nodes[0], &nodes[nodes.len() - 1]);
+    if is_loop {
+        Technique::ContinuousNiceLoop { length }
+    } else if first.kind == ChainNodeKind::Candidate
+        && last.kind == ChainNodeKind::Candidate
+        && first.nodes == last.nodes
+    {
+        Technique::DiscontinuousNiceLoop { length }
+    } else if has_kind(ChainNodeKind::Als) {
+        Technique::AlsAic { length }
+    } else if has_kind(ChainNodeKind::Group) {
+        Technique::GroupedAic { length }
+    } else {
+        Technique::Aic { length }
+    }
+}
+
+pub struct ChainEliminator {
+    pub kind: ChainKind,
+    // Max number of chain nodes in a chain.
+    pub max_length: usize,
+}
+
+impl ChainEliminator {
+    // Returns the shortest chains first. A chain is dropped if all its
+    // eliminations are made by shorter chains.
+    //
+    // The chains never start from ALS nodes to limit the search, so the chains
+    // with ALS nodes at both ends are not found.
+    pub fn find_chains(&self, node_arr: &NodeArray) -> Vec<Chain> {
+        let graph = ChainGraph::new(node_arr, self.kind);
+        let mut chains = Vec::new();
+        for start in 0..graph.nodes.len() {
+            if graph.nodes[start].kind != ChainNodeKind::Als {
+                graph.search(start, self.max_length, &mut chains);
+            }
+        }
+
+        chains.sort_by_key(|c| c.nodes.len());
+        let mut eliminated = HashSet::new();
+        chains.retain(|c| {
+            let mut has_new = false;
+            for &e in c.eliminations.iter() {
+                has_new |= eliminated.insert(e);
+            }
+            has_new
+        });
+        chains
+    }
+}
+
+impl StrategySolver for ChainEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        self.find_chains(node_arr)
+            .iter()
+            .map(|c| c.to_step())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    // Keeps color 1 only at the given nodes of the house.
+    fn keep_in_house(node_arr: &mut NodeArray, house: usize, nodes: &[NodeIndexType]) {
+        for idx in get_all_idx_for_house(house) {
+            if !nodes.contains(&idx) {
+                node_arr[idx].available_colors.del(1);
+            }
+        }
+    }
+
+    fn eliminations(chains: &[Chain]) -> Vec<Candidate> {
+        let mut ret = chains
+            .iter()
+            .flat_map(|c| c.eliminations.iter().copied())
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+
+    fn color_1_at(idx_arr: &[NodeIndexType]) -> Vec<Candidate> {
+        idx_arr
+            .iter()
+            .map(|&idx| Candidate { idx, color: 1 })
+            .collect()
+    }
+
+    #[test]
+    fn x_chain() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // r1c0 = r1c6 - r5c6 = r5c2.
+        keep_in_house(&mut node_arr, 1, &[9, 15]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 6, &[15, 51]);
+        keep_in_house(&mut node_arr, 5, &[47, 51]);
+
+        let solver = ChainEliminator {
+            kind: ChainKind::X,
+            max_length: 4,
+        };
+        let chains = solver.find_chains(&node_arr);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].technique, Technique::XChain { length: 4 });
+        assert_eq!(chains[0].nodes.len(), 4);
+        assert_eq!(eliminations(&chains), color_1_at(&[2, 20, 27, 36]));
+
+        let solver = ChainEliminator {
+            kind: ChainKind::X,
+            max_length: 3,
+        };
+        assert!(solver.find_chains(&node_arr).is_empty());
+    }
+
+    #[test]
+    fn x_loop() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // r0c0 = r0c4 - r4c4 = r4c0, and r4c0 - r0c0 closes the loop. It is
+        // the same as an X-Wing.
+        keep_in_house(&mut node_arr, 0, &[0, 4]);
+        keep_in_house(&mut node_arr, 4, &[36, 40]);
+
+        let solver = ChainEliminator {
+            kind: ChainKind::X,
+            max_length: 4,
+        };
+        let chains = solver.find_chains(&node_arr);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(
+            eliminations(&chains),
+            color_1_at(&[9, 13, 18, 22, 27, 31, 45, 49, 54, 58, 63, 67, 72, 76])
+        );
+    }
+
+    #[test]
+    fn xy_chain() {
+        // r0c0 {1, 2}, r0c5 {2, 3}, r4c5 {3, 4} and r4c8 {4, 1}.
+        let node_arr =
+            node_arr_with_candidates(&[(0, &[1, 2]), (5, &[2, 3]), (41, &[3, 4]), (44, &[1, 4])]);
+        let solver = ChainEliminator {
+            kind: ChainKind::Xy,
+            max_length: 8,
+        };
+        let chains = solver.find_chains(&node_arr);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].technique, Technique::XyChain { length: 8 });
+        assert_eq!(chains[0].to_step().nodes.len(), 4);
+        assert_eq!(eliminations(&chains), color_1_at(&[8, 36]));
+    }
+
+    #[test]
+    fn grouped_aic() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // r0c0 = r0c67 - r2c8 = r7c8, where r0c67 is a group.
+        keep_in_house(&mut node_arr, 0, &[0, 6, 7]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 8, &[26, 71]);
+
+        let solver = |grouped| ChainEliminator {
+            kind: ChainKind::Aic {
+                grouped,
+                als: false,
+            },
+            max_length: 6,
+        };
+        assert!(solver(false).find_chains(&node_arr).is_empty());
+        let chains = solver(true).find_chains(&node_arr);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].technique, Technique::GroupedAic { length: 4 });
+        assert_eq!(eliminations(&chains), color_1_at(&[63]));
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        let kinds = [
+            ChainKind::X,
+            ChainKind::Xy,
+            ChainKind::Aic {
+                grouped: true,
+                als: true,
+            },
+        ];
+        for kind in kinds {
+            let solver = ChainEliminator {
+                kind,
+                max_length: 10,
+            };
+            check_steps_keep_answer(&solver);
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Coloring works on clusters of candidates connected by strong links, i.e.
+// pairs of candidates where exactly one is the answer. Alternately coloring a
+// cluster splits it into two sides, one of which is all true and the other is
+// all false.
+
+const CANDIDATE_COUNT: usize = NODE_COUNT * COLOR_COUNT;
+
+fn candidate_id(c: &Candidate) -> usize {
+    c.idx * COLOR_COUNT + c.color as usize - 1
+}
+
+// Graph of the strong links between candidates.
+pub struct ConjugateGraph {
+    links: Vec<Vec<Candidate>>,
+}
+
+impl ConjugateGraph {
+    fn new() -> Self {
+        ConjugateGraph {
+            links: vec![Vec::new(); CANDIDATE_COUNT],
+        }
+    }
+
+    fn add_link(&mut self, a: Candidate, b: Candidate) {
+        if !self.links[candidate_id(&a)].contains(&b) {
+            self.links[candidate_id(&a)].push(b);
+            self.links[candidate_id(&b)].push(a);
+        }
+    }
+
+    fn add_conjugate_pairs(&mut self, node_arr: &NodeArray, color: ColorType) {
+        let candidates = get_nodes_with_available_color(node_arr, color);
+        for house in HOUSE_BITS_ARR.iter() {
+            let nodes = house.intersect(&candidates);
+            if nodes.count() != 2 {
+                continue;
+            }
+            let mut iter = nodes.iter();
+            let (a, b) = (iter.next().unwrap(), iter.next().unwrap());
+            self.add_link(Candidate { idx: a, color }, Candidate { idx: b, color });
+        }
+    }
+
+    // The conjugate pairs of a single color, i.e. houses holding the color in
+    // only two nodes.
+    pub fn for_color(node_arr: &NodeArray, color: ColorType) -> Self {
+        let mut ret = Self::new();
+        ret.add_conjugate_pairs(node_arr, color);
+        ret
+    }
+
+    // The conjugate pairs of all colors, plus the two candidates of each
+    // bivalue node.
+    pub fn for_all_colors(node_arr: &NodeArray) -> Self {
+        let mut ret = Self::new();
+        for color in 1..=COLOR_COUNT as ColorType {
+            ret.add_conjugate_pairs(node_arr, color);
+        }
+        for (idx, node) in node_arr.iter().enumerate() {
+            if node.color != 0 || node.available_colors.count() != 2 {
+                continue;
+            }
+            let colors = node.available_colors.get_all();
+            ret.add_link(
+                Candidate {
+                    idx,
+                    color: colors[0],
+                },
+                Candidate {
+                    idx,
+                    color: colors[1],
+                },
+            );
+        }
+        ret
+    }
+
+    pub fn links(&self, c: &Candidate) -> &[Candidate] {
+        &self.links[candidate_id(c)]
+    }
+
+    // Returns the connected clusters with at least one link.
+    pub fn clusters(&self) -> Vec<Cluster> {
+        let mut visited = vec![false; CANDIDATE_COUNT];
+        let mut ret = Vec::new();
+        for id in 0..CANDIDATE_COUNT {
+            if visited[id] || self.links[id].is_empty() {
+                continue;
+            }
+            let start = Candidate {
+                idx: id / COLOR_COUNT,
+                color: (id % COLOR_COUNT + 1) as ColorType,
+            };
+            let mut cluster = Cluster {
+                sides: [Vec::new(), Vec::new()],
+            };
+            let mut queue = vec![(start, 0)];
+            visited[id] = true;
+            while let Some((c, side)) = queue.pop() {
+                cluster.sides[side].push(c);
+                for next in self.links(&c) {
+                    if !visited[candidate_id(next)] {
+                        visited[candidate_id(next)] = true;
+                        queue.push((*next, 1 - side));
+                    }
+                }
+            }
+            ret.push(cluster);
+        }
+        ret
+    }
+}
+
+// A cluster of candidates connected by strong links. Either all the
+// candidates of sides[0] or all the candidates of sides[1] are the answers.
+pub struct Cluster {
+    pub sides: [Vec<Candidate>; 2],
+}
+
+// The candidates of a cluster side, grouped by colors.
+struct SideBits {
+    nodes: [NodeBits; COLOR_COUNT],
+    // Nodes seen by the candidates of each color.
+    seen: [NodeBits; COLOR_COUNT],
+}
+
+impl SideBits {
+    fn new(candidates: &[Candidate]) -> Self {
+        let mut ret = SideBits {
+            nodes: [NodeBits::new(); COLOR_COUNT],
+            seen: [NodeBits::new(); COLOR_COUNT],
+        };
+        for c in candidates {
+            let i = c.color as usize - 1;
+            ret.nodes[i].set(c.idx);
+            ret.seen[i] = ret.seen[i].union(&NEIGHBOR_BITS_MAP[c.idx]);
+        }
+        ret
+    }
+
+    fn all_nodes(&self) -> NodeBits {
+        self.nodes
+            .iter()
+            .fold(NodeBits::new(), |acc, n| acc.union(n))
+    }
+
+    // Whether two candidates of the same color see each other.
+    fn has_conflict(&self) -> bool {
+        (0..COLOR_COUNT).any(|i| !self.nodes[i].intersect(&self.seen[i]).is_empty())
+    }
+
+    // Whether a node holds more than one candidate of the side.
+    fn has_conflict_in_node(&self) -> bool {
+        let mut nodes = NodeBits::new();
+        for n in self.nodes.iter() {
+            if !nodes.intersect(n).is_empty() {
+                return true;
+            }
+            nodes = nodes.union(n);
+        }
+        false
+    }
+}
+
+fn to_candidates(nodes: &NodeBits, color: ColorType) -> impl Iterator<Item = Candidate> {
+    nodes.iter().map(move |idx| Candidate { idx, color })
+}
+
+fn cluster_step(technique: Technique, cluster: &Cluster, eliminations: Vec<Candidate>) -> Step {
+    let mut nodes = cluster
+        .sides
+        .iter()
+        .flatten()
+        .map(|c| c.idx)
+        .collect::<Vec<_>>();
+    nodes.sort();
+    nodes.dedup();
+    Step {
+        technique,
+        nodes,
+        eliminations,
+    }
+}
+
+// Eliminations of a cluster with the candidates of one color.
+fn simple_coloring_eliminations(
+    candidates: &NodeBits,
+    color: ColorType,
+    cluster: &Cluster,
+) -> Vec<Candidate> {
+    let sides = [
+        SideBits::new(&cluster.sides[0]),
+        SideBits::new(&cluster.sides[1]),
+    ];
+    let i = color as usize - 1;
+    // Color wrap: a side is false if two of its candidates see each other.
+    for (side, bits) in cluster.sides.iter().zip(sides.iter()) {
+        if bits.has_conflict() {
+            return side.clone();
+        }
+    }
+    // Color trap: candidates seeing both sides are false.
+    let trapped = candidates
+        .intersect(&sides[0].seen[i])
+        .intersect(&sides[1].seen[i])
+        .minus(&sides[0].nodes[i].union(&sides[1].nodes[i]));
+    to_candidates(&trapped, color).collect()
+}
+
+pub struct SimpleColoringEliminator {}
+
+impl StrategySolver for SimpleColoringEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for color in 1..=COLOR_COUNT as ColorType {
+            let candidates = get_nodes_with_available_color(node_arr, color);
+            for cluster in ConjugateGraph::for_color(node_arr, color).clusters() {
+                let eliminations = simple_coloring_eliminations(&candidates, color, &cluster);
+                if !eliminations.is_empty() {
+                    steps.push(cluster_step(
+                        Technique::SimpleColoring,
+                        &cluster,
+                        eliminations,
+                    ));
+                }
+            }
+        }
+        steps
+    }
+}
+
+// With two clusters of the same color, if a candidate of side a1 sees a
+// candidate of side a2, a1 and a2 cannot both be true. So one of the opposite
+// sides b1 and b2 is true, and the candidates seeing both b1 and b2 are false.
+pub struct MultiColoringEliminator {}
+
+impl StrategySolver for MultiColoringEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for color in 1..=COLOR_COUNT as ColorType {
+            let i = color as usize - 1;
+            let candidates = get_nodes_with_available_color(node_arr, color);
+            let clusters = ConjugateGraph::for_color(node_arr, color).clusters();
+            let side_bits = clusters
+                .iter()
+                .map(|c| [SideBits::new(&c.sides[0]), SideBits::new(&c.sides[1])])
+                .collect::<Vec<_>>();
+            for (x, cluster_1) in side_bits.iter().enumerate() {
+                for (y, cluster_2) in side_bits.iter().enumerate().skip(x + 1) {
+                    let mut eliminations = NodeBits::new();
+                    for s1 in 0..2 {
+                        for s2 in 0..2 {
+                            if cluster_1[s1].nodes[i]
+                                .intersect(&cluster_2[s2].seen[i])
+                                .is_empty()
+                            {
+                                continue;
+                            }
+                            eliminations = eliminations.union(
+                                &cluster_1[1 - s1].seen[i].intersect(&cluster_2[1 - s2].seen[i]),
+                            );
+                        }
+                    }
+                    let colored = cluster_1[0].all_nodes().union(&cluster_1[1].all_nodes());
+                    let colored = colored
+                        .union(&cluster_2[0].all_nodes())
+                        .union(&cluster_2[1].all_nodes());
+                    let eliminations = eliminations.intersect(&candidates).minus(&colored);
+                    if eliminations.is_empty() {
+                        continue;
+                    }
+                    let mut step = cluster_step(
+                        Technique::MultiColoring,
+                        &clusters[x],
+                        to_candidates(&eliminations, color).collect(),
+                    );
+                    step.nodes
+                        .extend(clusters[y].sides.iter().flatten().map(|c| c.idx));
+                    step.nodes.sort();
+                    step.nodes.dedup();
+                    steps.push(step);
+                }
+            }
+        }
+        steps
+    }
+}
+
+// 3D Medusa colors the clusters of strong links across all the colors,
+// including the links inside bivalue nodes.
+pub struct MedusaEliminator {}
+
+fn medusa_eliminations(node_arr: &NodeArray, cluster: &Cluster) -> Vec<Candidate> {
+    let sides = [
+        SideBits::new(&cluster.sides[0]),
+        SideBits::new(&cluster.sides[1]),
+    ];
+
+    // A side is false if two of its candidates are in the same node or see
+    // each other.
+    for (side, bits) in cluster.sides.iter().zip(sides.iter()) {
+        if bits.has_conflict_in_node() || bits.has_conflict() {
+            return side.clone();
+        }
+    }
+
+    let colored_nodes = [sides[0].all_nodes(), sides[1].all_nodes()];
+    let is_colored = |idx: NodeIndexType, color: ColorType| {
+        let i = color as usize - 1;
+        sides[0].nodes[i].has(idx) || sides[1].nodes[i].has(idx)
+    };
+
+    // A side is false if all the candidates of an uncolored node see the side.
+    for (side, bits) in cluster.sides.iter().zip(sides.iter()) {
+        for (idx, node) in node_arr.iter().enumerate() {
+            if node.color != 0 || colored_nodes[0].has(idx) || colored_nodes[1].has(idx) {
+                continue;
+            }
+            let emptied = node
+                .available_colors
+                .get_all()
+                .into_iter()
+                .all(|c| bits.seen[c as usize - 1].has(idx));
+            if emptied {
+                return side.clone();
+            }
+        }
+    }
+
+    let mut ret = Vec::new();
+    for (idx, node) in node_arr.iter().enumerate() {
+        if node.color != 0 {
+            continue;
+        }
+        let in_both_sides = colored_nodes[0].has(idx) && colored_nodes[1].has(idx);
+        for color in node.available_colors.get_all() {
+            if is_colored(idx, color) {
+                continue;
+            }
+            let i = color as usize - 1;
+            let eliminated =
+                // Both sides are in the node, so the other candidates are false.
+                in_both_sides
+                // The candidate sees both sides.
+                || (sides[0].seen[i].has(idx) && sides[1].seen[i].has(idx))
+                // One side is in the node, and the candidate sees the other side.
+                || (colored_nodes[0].has(idx) && sides[1].seen[i].has(idx))
+                || (colored_nodes[1].has(idx) && sides[0].seen[i].has(idx));
+            if eliminated {
+                ret.push(Candidate { idx, color });
+            }
+        }
+    }
+    ret
+}
+
+impl StrategySolver for MedusaEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for cluster in ConjugateGraph::for_all_colors(node_arr).clusters() {
+            let eliminations = medusa_eliminations(node_arr, &cluster);
+            if !eliminations.is_empty() {
+                steps.push(cluster_step(Technique::Medusa, &cluster, eliminations));
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    // Keeps color at the given nodes of the house.
+    fn keep_in_house(node_arr: &mut NodeArray, house: usize, color: ColorType, nodes: &[usize]) {
+        for idx in get_all_idx_for_house(house) {
+            if !nodes.contains(&idx) {
+                node_arr[idx].available_colors.del(color);
+            }
+        }
+    }
+
+    fn eliminated_nodes(steps: &[Step]) -> Vec<NodeIndexType> {
+        let mut ret = steps
+            .iter()
+            .flat_map(|s| s.eliminations.iter().map(|e| e.idx))
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn clusters() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        keep_in_house(&mut node_arr, 1, 1, &[9, 15]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 6, 1, &[15, 51]);
+
+        let clusters = ConjugateGraph::for_color(&node_arr, 1).clusters();
+        assert_eq!(clusters.len(), 1);
+        let mut sides = clusters[0]
+            .sides
+            .iter()
+            .map(|s| {
+                let mut nodes = s.iter().map(|c| c.idx).collect::<Vec<_>>();
+                nodes.sort();
+                nodes
+            })
+            .collect::<Vec<_>>();
+        sides.sort();
+        assert_eq!(sides, vec![vec![9, 51], vec![15]]);
+    }
+
+    #[test]
+    fn color_trap() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // r1c0 - r1c6 - r5c6 - r5c2.
+        keep_in_house(&mut node_arr, 1, 1, &[9, 15]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 6, 1, &[15, 51]);
+        keep_in_house(&mut node_arr, 5, 1, &[47, 51]);
+
+        let steps = SimpleColoringEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![2, 20, 27, 36]);
+    }
+
+    #[test]
+    fn color_wrap() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // r0c0 - r0c4 - r4c4 - r4c1 - r2c1, where r0c0 and r2c1 have the same
+        // color and are in the same square.
+        keep_in_house(&mut node_arr, 0, 1, &[0, 4]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 4, 1, &[4, 40]);
+        keep_in_house(&mut node_arr, 4, 1, &[37, 40]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 1, 1, &[19, 37]);
+
+        let steps = SimpleColoringEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![0, 19, 40]);
+    }
+
+    #[test]
+    fn multi_coloring() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // Cluster 1: r0c0 - r0c4. Cluster 2: r2c1 - r7c1. r0c0 sees r2c1, so
+        // either r0c4 or r7c1 is 1.
+        keep_in_house(&mut node_arr, 0, 1, &[0, 4]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 1, 1, &[19, 64]);
+
+        assert!(SimpleColoringEliminator {}.find_steps(&node_arr).is_empty());
+        let steps = MultiColoringEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![67]);
+    }
+
+    #[test]
+    fn medusa_two_colors_in_node() {
+        // r0c0 is {1, 2}, and row 0 holds 1 and 2 only at c0 and c5. r0c5 must
+        // be either 1 or 2.
+        let mut node_arr = node_arr_with_candidates(&[(0, &[1, 2])]);
+        keep_in_house(&mut node_arr, 0, 1, &[0, 5]);
+        keep_in_house(&mut node_arr, 0, 2, &[0, 5]);
+
+        let steps = MedusaEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].eliminations,
+            (3..=9)
+                .map(|color| Candidate { idx: 5, color })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer(&SimpleColoringEliminator {});
+        check_steps_keep_answer(&MultiColoringEliminator {});
+        check_steps_keep_answer(&MedusaEliminator {});
+    }
+}
@@ -1,4 +1,4 @@
-const RANK: usize = 3;
+pub(crate) const RANK: usize = 3;
 pub const COLOR_COUNT: usize = RANK * RANK;
 pub const NODE_COUNT: usize = COLOR_COUNT * COLOR_COUNT;
 
@@ -81,13 +81,7 @@ where
     }
 
     fn count_clues(&self) -> usize {
-        let mut cnt = 0;
-        for i in 0..NODE_COUNT {
-            if self[i].to_color() != 0 {
-                cnt += 1;
-            }
-        }
-        cnt
+        self.iter().filter(|c| c.to_color() != 0).count()
     }
 }
 
@@ -102,14 +96,36 @@ pub fn print_sudoku_array<T>(data: &[T; NODE_COUNT], printer: fn(&T) -> String)
     buffer
 }
 
+// Parses a puzzle from a string of 81 digits, where '0' or '.' is an empty
+// cell. Whitespace is ignored.
+pub fn parse_sudoku_array(src: &str) -> Option<ColorArray> {
+    let mut ret = [0; NODE_COUNT];
+    let mut cnt = 0;
+    for ch in src.chars().filter(|ch| !ch.is_whitespace()) {
+        if cnt == NODE_COUNT {
+            return None;
+        }
+        ret[cnt] = match ch {
+            '.' => 0,
+            '0'..='9' => ch as ColorType - b'0',
+            _ => return None,
+        };
+        cnt += 1;
+    }
+    if cnt != NODE_COUNT {
+        return None;
+    }
+    Some(ret)
+}
+
 // Helper functions for calculating index.
-const fn row_idx(idx: usize) -> usize {
+pub(crate) const fn row_idx(idx: usize) -> usize {
     idx / COLOR_COUNT
 }
-const fn col_idx(idx: usize) -> usize {
+pub(crate) const fn col_idx(idx: usize) -> usize {
     idx % COLOR_COUNT
 }
-const fn sqr_idx(idx: usize) -> usize {
+pub(crate) const fn sqr_idx(idx: usize) -> usize {
     row_idx(idx) / RANK * RANK + col_idx(idx) / RANK
 }
 
@@ -127,7 +143,7 @@ type NeighborArrayMap = SudokuArrayType<NeighborArray>;
 // Map from an index to the indexes of its numbers. This can be generated at the compile time.
 pub static NEIGHBOR_ARRAY_MAP: NeighborArrayMap = build_neigh_arr_map();
 
-const fn get_neighs_for_idx(idx: usize) -> NeighborArray {
+pub(crate) const fn get_neighs_for_idx(idx: usize) -> NeighborArray {
     let mut bit_array = [false; NODE_COUNT];
 
     let row = row_idx(idx);
@@ -207,3 +223,24 @@ pub(crate) const fn get_all_idx_for_sqr(sqr: usize) -> [NodeIndexType; COLOR_COU
     }
     ret
 }
+
+// Houses (rows, columns and squares) are numbered in a single range: rows are
+// 0..9, columns are 9..18 and squares are 18..27.
+pub(crate) const HOUSE_COUNT: usize = 3 * COLOR_COUNT;
+
+pub(crate) const fn get_all_idx_for_house(house: usize) -> [NodeIndexType; COLOR_COUNT] {
+    match house / COLOR_COUNT {
+        0 => get_all_idx_for_row(house % COLOR_COUNT),
+        1 => get_all_idx_for_col(house % COLOR_COUNT),
+        _ => get_all_idx_for_sqr(house % COLOR_COUNT),
+    }
+}
+
+// Returns the row, column and square house containing idx.
+pub(crate) const fn get_houses_for_idx(idx: NodeIndexType) -> [usize; 3] {
+    [
+        row_idx(idx),
+        COLOR_COUNT + col_idx(idx),
+        2 * COLOR_COUNT + sqr_idx(idx),
+    ]
+}
@@ -0,0 +1,210 @@
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+
+use crate::*;
+use generator::*;
+use rating::*;
+use scorer::ScorerConfig;
+
+// Difficulty tiers, defined by the hardest technique needed to solve the
+// puzzle with the easiest steps, and by how many steps need it.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+    Extreme,
+}
+
+// The max SE rating of the steps of each tier, and the max number of steps
+// harder than the previous tier. A puzzle exceeding either one is in a harder
+// tier. The puzzles which cannot be solved by the techniques are Extreme.
+const TIERS: [(Difficulty, i32, usize); 5] = [
+    // Hidden singles.
+    (Difficulty::Beginner, 15, usize::MAX),
+    // Naked singles.
+    (Difficulty::Easy, 23, usize::MAX),
+    // Intersections, subsets up to triples, X-Wings and Swordfishes.
+    (Difficulty::Medium, 40, 6),
+    // Wings, quads, Jellyfishes and the uniqueness techniques.
+    (Difficulty::Hard, 56, 6),
+    // Single digit patterns, coloring and chains.
+    (Difficulty::Expert, 72, 8),
+];
+
+impl Difficulty {
+    // The number of clues to generate puzzles of the tier with.
+    fn target_clues_num(&self) -> NodeIndexType {
+        match self {
+            Difficulty::Beginner => 40,
+            Difficulty::Easy => 34,
+            Difficulty::Medium => 28,
+            Difficulty::Hard => 25,
+            Difficulty::Expert => 22,
+            Difficulty::Extreme => 17,
+        }
+    }
+
+    // The min and max SE ratings of the hardest step of the puzzles of the
+    // tier.
+    pub(crate) fn rating_bounds(&self) -> (i32, i32) {
+        let i = TIERS
+            .iter()
+            .position(|t| t.0 == *self)
+            .unwrap_or(TIERS.len());
+        let min_rating = if i == 0 { 0 } else { TIERS[i - 1].1 + 1 };
+        let max_rating = TIERS.get(i).map_or(i32::MAX, |t| t.1);
+        (min_rating, max_rating)
+    }
+}
+
+// The tier of a puzzle with the evidence.
+#[derive(Clone, Debug)]
+pub struct Classification {
+    pub difficulty: Difficulty,
+    // SE rating of the hardest step, or UNSOLVED_RATING.
+    pub rating: i32,
+    pub hardest: Option<RatedStep>,
+    // Number of steps harder than the max rating of the previous tier.
+    pub hard_steps: usize,
+    pub solved: bool,
+}
+
+pub fn classify(puzzle: &ColorArray) -> Classification {
+    let solve = rated_solve(puzzle, &ScorerConfig::default());
+    let hardest = solve.steps.iter().rev().max_by_key(|s| s.rating).cloned();
+    let rating = if solve.solved {
+        hardest.as_ref().map_or(0, |s| s.rating)
+    } else {
+        UNSOLVED_RATING
+    };
+    let hard_steps = |min_rating| solve.steps.iter().filter(|s| s.rating > min_rating).count();
+
+    let mut prev_rating = 0;
+    for (difficulty, max_rating, max_steps) in TIERS {
+        let cnt = hard_steps(prev_rating);
+        if solve.solved && rating <= max_rating && cnt <= max_steps {
+            return Classification {
+                difficulty,
+                rating,
+                hardest,
+                hard_steps: cnt,
+                solved: solve.solved,
+            };
+        }
+        prev_rating = max_rating;
+    }
+    Classification {
+        difficulty: Difficulty::Extreme,
+        rating,
+        hardest,
+        hard_steps: hard_steps(prev_rating),
+        solved: solve.solved,
+    }
+}
+
+// Tries of generate_puzzle_with_difficulty made in a second, about.
+const DIFFICULTY_TRIES_PER_SEC: f64 = 2.0;
+
+// Generates puzzles from the answer until one is in the tier, or returns the
+// one in the closest tier after the timeout. Each try uses the next seed. With
+// a seed, the timeout is turned into a number of tries, see TryBudget.
+pub fn generate_puzzle_with_difficulty(
+    answer: &ColorArray,
+    difficulty: Difficulty,
+    timeout: Duration,
+    seed: Option<u64>,
+) -> (ColorArray, Classification) {
+    let config = GeneratorConfig {
+        timeout: Some(timeout),
+        target_clues_num: difficulty.target_clues_num(),
+        seed,
+        ..Default::default()
+    };
+    let mut budget = TryBudget::new(&config, DIFFICULTY_TRIES_PER_SEC);
+    let mut best: Option<(ColorArray, Classification)> = None;
+    for i in 0.. {
+        let puzzle = generate_puzzle_from_answer(
+            answer,
+            GeneratorConfig {
+                timeout: budget.nested_timeout(),
+                seed: seed.map(|seed| seed.wrapping_add(i)),
+                ..config
+            },
+        );
+        budget.add_try();
+        let classification = classify(&puzzle);
+        let distance = |c: &Classification| (c.difficulty as i32 - difficulty as i32).abs();
+        if best
+            .as_ref()
+            .is_none_or(|(_, b)| distance(&classification) < distance(b))
+        {
+            best = Some((puzzle, classification));
+        }
+        let (_, b) = best.as_ref().unwrap();
+        if b.difficulty == difficulty || budget.is_exhausted() {
+            break;
+        }
+    }
+    best.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn classify_test_puzzles() {
+        let classify = |i: usize| classify(&parse_sudoku_array(TEST_PUZZLES[i]).unwrap());
+        let hard = classify(0);
+        assert!(hard.solved);
+        assert_eq!(hard.difficulty, Difficulty::Hard);
+        assert_eq!(hard.hardest.unwrap().name, "wxyz_wing");
+        // Cannot be solved without backtracking.
+        let extreme = classify(7);
+        assert!(!extreme.solved);
+        assert_eq!(extreme.difficulty, Difficulty::Extreme);
+    }
+
+    #[test]
+    fn classification_fits_tier() {
+        for src in TEST_PUZZLES[..6].iter() {
+            let classification = classify(&parse_sudoku_array(src).unwrap());
+            let Some(i) = TIERS.iter().position(|t| t.0 == classification.difficulty) else {
+                continue;
+            };
+            assert!(classification.rating <= TIERS[i].1);
+            assert!(classification.hard_steps <= TIERS[i].2);
+        }
+    }
+
+    #[test]
+    fn generate_beginner() {
+        let answer = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        let (puzzle, classification) = generate_puzzle_with_difficulty(
+            &answer,
+            Difficulty::Beginner,
+            Duration::from_secs(3),
+            None,
+        );
+        assert_eq!(classification.difficulty, Difficulty::Beginner);
+        assert_eq!(classify(&puzzle).difficulty, Difficulty::Beginner);
+    }
+
+    #[test]
+    fn same_seed_same_puzzle_by_timeout() {
+        // With no time, a single try is made, which is not Expert with this
+        // seed.
+        let answer = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        let generate = || {
+            generate_puzzle_with_difficulty(&answer, Difficulty::Expert, Duration::ZERO, Some(1))
+        };
+        let (puzzle, classification) = generate();
+        assert_ne!(classification.difficulty, Difficulty::Expert);
+        assert_eq!(generate().0, puzzle);
+    }
+}
@@ -0,0 +1,575 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Pattern techniques which are only needed by the hardest puzzles that can be
+// solved without trial and error. They are expensive, so the scorer does not
+// use them unless asked to.
+
+fn color_bit(color: ColorType) -> u16 {
+    1 << (color - 1)
+}
+
+// Returns the node at the row and column, or at the column and row if
+// transposed, so that the patterns along rows can be reused along columns.
+fn node_at(transposed: bool, row: usize, col: usize) -> NodeIndexType {
+    if transposed {
+        col * COLOR_COUNT + row
+    } else {
+        row * COLOR_COUNT + col
+    }
+}
+
+// Returns the available colors of an uncolored node, or 0.
+fn candidates(node_arr: &NodeArray, idx: NodeIndexType) -> u16 {
+    if node_arr[idx].color == 0 {
+        node_arr[idx].available_colors.bits()
+    } else {
+        0
+    }
+}
+
+// Returns the colors of the node, either as a given or as candidates.
+fn colors_or_given(node_arr: &NodeArray, idx: NodeIndexType) -> u16 {
+    match node_arr[idx].color {
+        0 => node_arr[idx].available_colors.bits(),
+        color => color_bit(color),
+    }
+}
+
+// Returns the eliminations of the colors in `mask` from the nodes.
+fn eliminations_of(
+    node_arr: &NodeArray,
+    idx_arr: impl Iterator<Item = NodeIndexType>,
+    mask: u16,
+) -> Vec<Candidate> {
+    let mut ret = Vec::new();
+    for idx in idx_arr {
+        for color in mask_to_colors(candidates(node_arr, idx) & mask) {
+            ret.push(Candidate { idx, color });
+        }
+    }
+    ret
+}
+
+// A Junior Exocet has two base nodes in a mini-row of a square, holding 3 or 4
+// base colors together, and two target nodes in the other squares of the
+// band, in different rows which are not the base row. The cross lines are the
+// columns of the targets and the column of the base mini-row without a base
+// node. The pattern needs:
+// - Inside the band, the other node of each target's column in the target
+//   rows (the companion of the target) has no base color.
+// - Outside the band, the nodes of each base color in the cross lines can be
+//   covered by two rows.
+//
+// A base color x in a base node is not in the other rows of the base square
+// or row, and each cross line must have x in a row not covering it. That can
+// only be a target, so the two colors of the base nodes are the colors of the
+// targets. The compatibility test keeps the colors of the base and target
+// nodes for which such an assignment exists.
+pub struct JuniorExocetEliminator {}
+
+impl JuniorExocetEliminator {
+    fn find_in_band(
+        &self,
+        node_arr: &NodeArray,
+        transposed: bool,
+        band: usize,
+        steps: &mut Vec<Step>,
+    ) {
+        let at = |row, col| node_at(transposed, row, col);
+        let band_rows = band * RANK..band * RANK + RANK;
+        for base_row in band_rows.clone() {
+            let other_rows = band_rows
+                .clone()
+                .filter(|&r| r != base_row)
+                .collect::<Vec<_>>();
+            for stack in 0..RANK {
+                let other_stacks = (0..RANK).filter(|&s| s != stack).collect::<Vec<_>>();
+                for skipped in 0..RANK {
+                    let cross_col = stack * RANK + skipped;
+                    let base_cols = (stack * RANK..stack * RANK + RANK)
+                        .filter(|&c| c != cross_col)
+                        .collect::<Vec<_>>();
+                    let base = [at(base_row, base_cols[0]), at(base_row, base_cols[1])];
+                    if base.iter().any(|&idx| node_arr[idx].color != 0) {
+                        continue;
+                    }
+                    let base_colors = candidates(node_arr, base[0]) | candidates(node_arr, base[1]);
+                    if !(3..=4).contains(&base_colors.count_ones()) {
+                        continue;
+                    }
+                    for (t1_row, t2_row) in [
+                        (other_rows[0], other_rows[1]),
+                        (other_rows[1], other_rows[0]),
+                    ] {
+                        for t1_col in other_stacks[0] * RANK..other_stacks[0] * RANK + RANK {
+                            for t2_col in other_stacks[1] * RANK..other_stacks[1] * RANK + RANK {
+                                let targets = [at(t1_row, t1_col), at(t2_row, t2_col)];
+                                let companions = [at(t2_row, t1_col), at(t1_row, t2_col)];
+                                if targets
+                                    .iter()
+                                    .any(|&idx| candidates(node_arr, idx) & base_colors == 0)
+                                    || companions.iter().any(|&idx| {
+                                        colors_or_given(node_arr, idx) & base_colors != 0
+                                    })
+                                    || !self.covered_by_two_rows(
+                                        node_arr,
+                                        &at,
+                                        band,
+                                        [cross_col, t1_col, t2_col],
+                                        base_colors,
+                                    )
+                                {
+                                    continue;
+                                }
+                                if let Some(step) = self.compatibility_test(node_arr, base, targets)
+                                {
+                                    steps.push(step);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Whether the nodes of each color outside the band in the cross lines
+    // can be covered by two rows.
+    fn covered_by_two_rows(
+        &self,
+        node_arr: &NodeArray,
+        at: &dyn Fn(usize, usize) -> NodeIndexType,
+        band: usize,
+        cross_cols: [usize; 3],
+        colors: u16,
+    ) -> bool {
+        mask_to_colors(colors).all(|color| {
+            let rows = (0..COLOR_COUNT)
+                .filter(|&row| row / RANK != band)
+                .filter(|&row| {
+                    cross_cols
+                        .iter()
+                        .any(|&col| colors_or_given(node_arr, at(row, col)) & color_bit(color) != 0)
+                })
+                .count();
+            rows <= 2
+        })
+    }
+
+    fn compatibility_test(
+        &self,
+        node_arr: &NodeArray,
+        base: [NodeIndexType; 2],
+        targets: [NodeIndexType; 2],
+    ) -> Option<Step> {
+        let nodes = [base[0], base[1], targets[0], targets[1]];
+        let colors = nodes.map(|idx| candidates(node_arr, idx));
+        // Colors which can be kept at each node.
+        let mut kept = [0u16; 4];
+        for x in mask_to_colors(colors[0]) {
+            for y in mask_to_colors(colors[1] & !color_bit(x)) {
+                for (u, v) in [(x, y), (y, x)] {
+                    if colors[2] & color_bit(u) != 0 && colors[3] & color_bit(v) != 0 {
+                        kept[0] |= color_bit(x);
+                        kept[1] |= color_bit(y);
+                        kept[2] |= color_bit(u);
+                        kept[3] |= color_bit(v);
+                    }
+                }
+            }
+        }
+        if kept[0] == 0 {
+            // The puzzle has no answer.
+            return None;
+        }
+        let mut eliminations = Vec::new();
+        for (&idx, &mask) in nodes.iter().zip(kept.iter()) {
+            eliminations.append(&mut eliminations_of(node_arr, std::iter::once(idx), !mask));
+        }
+        if eliminations.is_empty() {
+            return None;
+        }
+        Some(Step {
+            technique: Technique::JuniorExocet,
+            nodes: nodes.to_vec(),
+            eliminations,
+        })
+    }
+}
+
+impl StrategySolver for JuniorExocetEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for transposed in [false, true] {
+            for band in 0..RANK {
+                self.find_in_band(node_arr, transposed, band, &mut steps);
+            }
+        }
+        steps
+    }
+}
+
+// An SK-Loop is formed around four solved pivots at r1c1, r1c2, r2c1 and r2c2
+// in four different squares. In the square of each pivot, the two other nodes
+// in its row and the two other nodes in its column make two pairs, and the 8
+// pairs make a loop: the row pairs of a pivot row are linked by the row, the
+// column pairs of a pivot column by the column, and the row and column pairs
+// of a square by the square.
+//
+// If each pair only holds the colors of its two links, with no color in two
+// consecutive links and no empty link, each color of a link is the answer of at most one node
+// of its two pairs. When the links hold 16 colors in total, all of them are
+// needed by the 16 nodes, so the colors of each link can be eliminated from
+// the rest of its house. The eliminations of all the ways to split the pair
+// colors into such links make a single step for the loop.
+pub struct SkLoopEliminator {}
+
+impl SkLoopEliminator {
+    fn find_loop(
+        &self,
+        node_arr: &NodeArray,
+        rows: [usize; 2],
+        cols: [usize; 2],
+        steps: &mut Vec<Step>,
+    ) {
+        let at = |row, col| node_at(false, row, col);
+        let row_pair = |row: usize, col: usize| {
+            let mut idx_arr = (col / RANK * RANK..col / RANK * RANK + RANK)
+                .filter(|&c| c != col)
+                .map(|c| at(row, c));
+            [idx_arr.next().unwrap(), idx_arr.next().unwrap()]
+        };
+        let col_pair = |col: usize, row: usize| {
+            let mut idx_arr = (row / RANK * RANK..row / RANK * RANK + RANK)
+                .filter(|&r| r != row)
+                .map(|r| at(r, col));
+            [idx_arr.next().unwrap(), idx_arr.next().unwrap()]
+        };
+        let [r1, r2] = rows;
+        let [c1, c2] = cols;
+        let sqr = |row, col| sqr_idx(at(row, col)) + COLOR_COUNT * 2;
+        // Link k connects pairs[k] and pairs[k + 1] in houses[k].
+        let pairs = [
+            row_pair(r1, c1),
+            row_pair(r1, c2),
+            col_pair(c2, r1),
+            col_pair(c2, r2),
+            row_pair(r2, c2),
+            row_pair(r2, c1),
+            col_pair(c1, r2),
+            col_pair(c1, r1),
+        ];
+        let houses = [
+            r1,
+            sqr(r1, c2),
+            c2 + COLOR_COUNT,
+            sqr(r2, c2),
+            r2,
+            sqr(r2, c1),
+            c1 + COLOR_COUNT,
+            sqr(r1, c1),
+        ];
+        if pairs.iter().flatten().any(|&idx| node_arr[idx].color != 0) {
+            return;
+        }
+        let pair_colors =
+            pairs.map(|pair| candidates(node_arr, pair[0]) | candidates(node_arr, pair[1]));
+
+        // Each link holds the colors of its first pair which are not in the
+        // previous link, so the links are decided by the last one.
+        let last_candidates = pair_colors[7] & pair_colors[0];
+        let pattern = NodeBits::from_idx_arr(&pairs.concat());
+        let mut eliminations = Vec::new();
+        let mut last = last_candidates;
+        loop {
+            let mut links = [0u16; 8];
+            let mut prev = last;
+            let mut valid = true;
+            for k in 0..7 {
+                links[k] = pair_colors[k] & !prev;
+                valid &= links[k] & !pair_colors[k + 1] == 0;
+                prev = links[k];
+            }
+            links[7] = pair_colors[7] & !prev;
+            valid &= links[7] == last && links.iter().all(|&l| l != 0);
+            let total = links.iter().map(|l| l.count_ones()).sum::<u32>();
+            if valid && total == 16 {
+                for (&house, &link) in houses.iter().zip(links.iter()) {
+                    let rest = get_all_idx_for_house(house)
+                        .into_iter()
+                        .filter(|&idx| !pattern.has(idx));
+                    eliminations.append(&mut eliminations_of(node_arr, rest, link));
+                }
+            }
+            if last == 0 {
+                break;
+            }
+            last = (last - 1) & last_candidates;
+        }
+        if !eliminations.is_empty() {
+            eliminations.sort();
+            eliminations.dedup();
+            steps.push(Step {
+                technique: Technique::SkLoop,
+                nodes: pairs.concat(),
+                eliminations,
+            });
+        }
+    }
+}
+
+impl StrategySolver for SkLoopEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let lines = || {
+            (0..COLOR_COUNT).flat_map(|a| {
+                (a + 1..COLOR_COUNT)
+                    .filter(move |&b| a / RANK != b / RANK)
+                    .map(move |b| [a, b])
+            })
+        };
+        for rows in lines() {
+            for cols in lines() {
+                let pivots = [
+                    node_at(false, rows[0], cols[0]),
+                    node_at(false, rows[0], cols[1]),
+                    node_at(false, rows[1], cols[0]),
+                    node_at(false, rows[1], cols[1]),
+                ];
+                if pivots.iter().all(|&idx| node_arr[idx].color != 0) {
+                    self.find_loop(node_arr, rows, cols, &mut steps);
+                }
+            }
+        }
+        steps
+    }
+}
+
+// A Multi-Sector Locked Set picks some rows and columns, and the uncolored
+// nodes in both of them. A color can be the answer of at most one of those
+// nodes in each row, and in each column, so it is the answer of at most
+// min(#rows, #columns) of them, counting only the rows and columns where the
+// color is available in the nodes. If these limits add up to the number of
+// nodes, every color reaches its limit: it is the answer of a node in each of
+// the counted rows, or columns if there are fewer of them. So it can be
+// eliminated from the rest of those rows or columns.
+pub struct MslsEliminator {
+    // Max number of rows and of columns.
+    pub max_size: usize,
+}
+
+impl MslsEliminator {
+    fn find_locked_set(&self, node_arr: &NodeArray, rows: u16, cols: u16, steps: &mut Vec<Step>) {
+        let mut nodes = Vec::new();
+        // Rows and columns where each color is available in the nodes.
+        let mut color_rows = [0u16; COLOR_COUNT];
+        let mut color_cols = [0u16; COLOR_COUNT];
+        for row in mask_to_lines(rows) {
+            for col in mask_to_lines(cols) {
+                let idx = node_at(false, row, col);
+                for color in mask_to_colors(candidates(node_arr, idx)) {
+                    color_rows[color as usize - 1] |= 1 << row;
+                    color_cols[color as usize - 1] |= 1 << col;
+                }
+                if node_arr[idx].color == 0 {
+                    nodes.push(idx);
+                }
+            }
+        }
+        let limit = |c: usize| color_rows[c].count_ones().min(color_cols[c].count_ones());
+        if nodes.is_empty() || (0..COLOR_COUNT).map(limit).sum::<u32>() as usize != nodes.len() {
+            return;
+        }
+
+        let pattern = NodeBits::from_idx_arr(&nodes);
+        let mut eliminations = Vec::new();
+        for c in 0..COLOR_COUNT {
+            let (row_count, col_count) = (color_rows[c].count_ones(), color_cols[c].count_ones());
+            let mut houses = Vec::new();
+            if row_count <= col_count {
+                houses.extend(mask_to_lines(color_rows[c]));
+            }
+            if col_count <= row_count {
+                houses.extend(mask_to_lines(color_cols[c]).map(|col| col + COLOR_COUNT));
+            }
+            for house in houses {
+                let rest = get_all_idx_for_house(house)
+                    .into_iter()
+                    .filter(|&idx| !pattern.has(idx));
+                eliminations.append(&mut eliminations_of(node_arr, rest, 1 << c));
+            }
+        }
+        if !eliminations.is_empty() {
+            eliminations.sort();
+            eliminations.dedup();
+            steps.push(Step {
+                technique: Technique::Msls,
+                nodes,
+                eliminations,
+            });
+        }
+    }
+}
+
+// Returns the lines of a bit mask, with bit i for line i.
+fn mask_to_lines(mask: u16) -> impl Iterator<Item = usize> {
+    (0..COLOR_COUNT).filter(move |i| mask & (1 << i) != 0)
+}
+
+impl StrategySolver for MslsEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let line_sets = (0..1u16 << COLOR_COUNT)
+            .filter(|mask| (2..=self.max_size).contains(&(mask.count_ones() as usize)))
+            .collect::<Vec<_>>();
+        let mut steps = Vec::new();
+        for &rows in line_sets.iter() {
+            for &cols in line_sets.iter() {
+                self.find_locked_set(node_arr, rows, cols, &mut steps);
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn del_colors(node_arr: &mut NodeArray, idx_arr: &[NodeIndexType], colors: &[ColorType]) {
+        for &idx in idx_arr {
+            for &color in colors {
+                node_arr[idx].available_colors.del(color);
+            }
+        }
+    }
+
+    fn has_elimination(steps: &[Step], idx: NodeIndexType, color: ColorType) -> bool {
+        steps
+            .iter()
+            .any(|s| s.eliminations.contains(&Candidate { idx, color }))
+    }
+
+    #[test]
+    fn junior_exocet() {
+        // Base: r0c0 and r0c1 {1, 2, 3}. Targets: r1c3 {1, 4, 5} and r2c6
+        // {1, 2, 7}. Companions: r2c3 and r1c6 without base colors. Outside the
+        // first band, the cross lines c2, c3 and c6 only have the base colors
+        // in r3 and r4.
+        let mut node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2, 3]),
+            (1, &[1, 2, 3]),
+            (12, &[1, 4, 5]),
+            (24, &[1, 2, 7]),
+        ]);
+        del_colors(&mut node_arr, &[21, 15], &[1, 2, 3]);
+        for row in 5..COLOR_COUNT {
+            let idx_arr = [2, 3, 6].map(|col| row * COLOR_COUNT + col);
+            del_colors(&mut node_arr, &idx_arr, &[1, 2, 3]);
+        }
+        let steps = JuniorExocetEliminator {}.find_steps(&node_arr);
+        let step = steps
+            .iter()
+            .find(|s| s.nodes == vec![0, 1, 12, 24])
+            .unwrap();
+        // The targets can only be 1 and 2, so the base cannot have 3.
+        let expected = [(0, 3), (1, 3), (12, 4), (12, 5), (24, 1), (24, 7)]
+            .map(|(idx, color)| Candidate { idx, color });
+        assert_eq!(step.eliminations, expected.to_vec());
+    }
+
+    #[test]
+    fn sk_loop() {
+        // Pivots at r0c0, r0c4, r4c0 and r4c4. Each pair holds the colors of
+        // its two links, which only split one way.
+        let mut node_arr = node_arr_with_candidates(&[]);
+        for (idx, color) in [(0, 9), (4, 8), (36, 8), (40, 9)] {
+            node_arr[idx].color = color;
+        }
+        let links: [&[ColorType]; 8] = [
+            &[1, 2],
+            &[3, 4],
+            &[1, 5],
+            &[2, 6],
+            &[3, 7],
+            &[4, 5],
+            &[6, 7],
+            &[3, 5],
+        ];
+        let pairs = [
+            [1, 2],
+            [3, 5],
+            [13, 22],
+            [31, 49],
+            [39, 41],
+            [37, 38],
+            [27, 45],
+            [9, 18],
+        ];
+        for (k, pair) in pairs.iter().enumerate() {
+            let kept = [links[(k + 7) % 8], links[k]].concat();
+            let others = (1..=COLOR_COUNT as ColorType)
+                .filter(|c| !kept.contains(c))
+                .collect::<Vec<_>>();
+            del_colors(&mut node_arr, pair, &others);
+        }
+        let steps = SkLoopEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        // Each link loses its colors from the rest of its house.
+        let rests: [&[NodeIndexType]; 8] = [
+            &[6, 7, 8],
+            &[12, 14, 21, 23],
+            &[58, 67, 76],
+            &[30, 32, 48, 50],
+            &[42, 43, 44],
+            &[28, 29, 46, 47],
+            &[54, 63, 72],
+            &[10, 11, 19, 20],
+        ];
+        let mut expected = Vec::new();
+        for (rest, link) in rests.iter().zip(links.iter()) {
+            for &idx in rest.iter() {
+                for &color in link.iter() {
+                    expected.push(Candidate { idx, color });
+                }
+            }
+        }
+        expected.sort();
+        assert_eq!(steps[0].eliminations, expected);
+    }
+
+    #[test]
+    fn msls() {
+        // r0c0 {1, 2}, r0c4 {1, 3}, r4c0 {2, 4} and r4c4 {3, 4}: 1 and 4 are
+        // limited by a row, and 2 and 3 by a column.
+        let node_arr =
+            node_arr_with_candidates(&[(0, &[1, 2]), (4, &[1, 3]), (36, &[2, 4]), (40, &[3, 4])]);
+        let steps = MslsEliminator { max_size: 2 }.find_steps(&node_arr);
+        let step = steps
+            .iter()
+            .find(|s| s.nodes == vec![0, 4, 36, 40])
+            .unwrap();
+        assert!(has_elimination(&steps, 8, 1));
+        assert!(has_elimination(&steps, 44, 4));
+        assert!(has_elimination(&steps, 72, 2));
+        assert!(has_elimination(&steps, 76, 3));
+        assert!(!step.eliminations.contains(&Candidate { idx: 8, color: 2 }));
+        assert_eq!(step.eliminations.len(), 4 * 7);
+    }
+
+    // Puzzles with Junior Exocets left by the basic solvers.
+    const JUNIOR_EXOCET_PUZZLES: [&str; 3] = [
+        "12.3....435....1....4........54..2..6...7.........8.9...31..5.......9.7.....6...8",
+        "12.3.....4.....3....3.5......42..5......8...9.6...5.7...15..2......9..6......7..8",
+        ".2.4.37.........32........4.4.2...7.8...5.........1...5.....9...3.9....7..1..86..",
+    ];
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer_on(&JUNIOR_EXOCET_PUZZLES, &JuniorExocetEliminator {});
+        check_steps_keep_answer(&SkLoopEliminator {});
+        check_steps_keep_answer(&MslsEliminator { max_size: 4 });
+    }
+}
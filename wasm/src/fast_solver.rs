@@ -98,7 +98,7 @@ impl FastSolver<'_> {
             }
         }
 
-        return Some(new_fill_candidates);
+        Some(new_fill_candidates)
     }
 
     fn eliminate_and_fill(&mut self, idx: Option<NodeIndexType>) -> Option<SolveResult> {
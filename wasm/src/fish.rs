@@ -0,0 +1,385 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// A fish of size n on a color picks n houses as the base sets, whose
+// candidates of the color do not overlap, and n houses as the cover sets. Each
+// base set holds the color exactly once, so if all the base candidates are in
+// the cover sets, each cover set holds the color inside the base sets, and
+// the other candidates in the cover sets can be eliminated.
+//
+// The base candidates outside the cover sets are fins. One of the fins may be
+// the answer, so with fins only the candidates that see all the fins can be
+// eliminated.
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FishKind {
+    // Rows as the base sets and columns as the cover sets, or vice versa.
+    Basic,
+    // A basic fish with squares added to the base or cover sets.
+    Franken,
+    // Any houses can be used in both the base and cover sets.
+    Mutant,
+}
+
+const ROW_HOUSES: u32 = (1 << COLOR_COUNT) - 1;
+const COL_HOUSES: u32 = ROW_HOUSES << COLOR_COUNT;
+const SQR_HOUSES: u32 = ROW_HOUSES << (2 * COLOR_COUNT);
+
+impl FishKind {
+    // Returns the pairs of (base houses, cover houses) which can be used.
+    fn house_sets(&self) -> &'static [(u32, u32)] {
+        match self {
+            FishKind::Basic => &[(ROW_HOUSES, COL_HOUSES), (COL_HOUSES, ROW_HOUSES)],
+            FishKind::Franken => &[
+                (ROW_HOUSES | SQR_HOUSES, COL_HOUSES | SQR_HOUSES),
+                (COL_HOUSES | SQR_HOUSES, ROW_HOUSES | SQR_HOUSES),
+            ],
+            FishKind::Mutant => &[(
+                ROW_HOUSES | COL_HOUSES | SQR_HOUSES,
+                ROW_HOUSES | COL_HOUSES | SQR_HOUSES,
+            )],
+        }
+    }
+
+    // Returns the most basic kind of fish the houses form.
+    fn classify(base: u32, cover: u32) -> FishKind {
+        let has = |houses: u32, kind: u32| houses & kind != 0;
+        let row_based = !has(base, COL_HOUSES) && !has(cover, ROW_HOUSES);
+        let col_based = !has(base, ROW_HOUSES) && !has(cover, COL_HOUSES);
+        if !row_based && !col_based {
+            FishKind::Mutant
+        } else if has(base | cover, SQR_HOUSES) {
+            FishKind::Franken
+        } else {
+            FishKind::Basic
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fish {
+    pub color: ColorType,
+    pub kind: FishKind,
+    pub base_houses: Vec<usize>,
+    pub cover_houses: Vec<usize>,
+    pub fins: Vec<NodeIndexType>,
+    pub eliminations: Vec<NodeIndexType>,
+}
+
+impl Fish {
+    pub fn to_step(&self, node_arr: &NodeArray) -> Step {
+        let mut nodes = NodeBits::new();
+        for &h in self.base_houses.iter() {
+            nodes = nodes.union(&HOUSE_BITS_ARR[h]);
+        }
+        let nodes = nodes.intersect(&get_nodes_with_available_color(node_arr, self.color));
+        Step {
+            technique: Technique::Fish {
+                size: self.base_houses.len(),
+                finned: !self.fins.is_empty(),
+                kind: self.kind,
+            },
+            nodes: nodes.iter().collect(),
+            eliminations: self
+                .eliminations
+                .iter()
+                .map(|&idx| Candidate {
+                    idx,
+                    color: self.color,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct FishEliminator {
+    pub size: usize,
+    pub max_fins: usize,
+    // Only the fishes of exactly this kind are reported, so that the solvers of
+    // different kinds can be weighted separately.
+    pub kind: FishKind,
+    // Max number of base sets examined per color. Franken and mutant fishes
+    // have a large search space, so the search stops once it is used up.
+    pub budget: usize,
+}
+
+struct FishSearch<'a> {
+    solver: &'a FishEliminator,
+    color: ColorType,
+    // Candidates of the color in each house. Empty if the color is already
+    // filled in the house.
+    house_candidates: [NodeBits; HOUSE_COUNT],
+    base_houses_allowed: u32,
+    cover_houses_allowed: u32,
+    budget: usize,
+    fishes: Vec<Fish>,
+}
+
+impl FishSearch<'_> {
+    fn search_base(&mut self, start: usize, base: u32, base_candidates: NodeBits) {
+        if base.count_ones() as usize == self.solver.size {
+            if self.budget == 0 {
+                return;
+            }
+            self.budget -= 1;
+            self.search_cover(base, base_candidates, base_candidates, 0, 0);
+            return;
+        }
+        for h in start..HOUSE_COUNT {
+            if self.budget == 0 {
+                return;
+            }
+            if (1 << h) & self.base_houses_allowed == 0 {
+                continue;
+            }
+            let candidates = self.house_candidates[h];
+            if candidates.is_empty() || !candidates.intersect(&base_candidates).is_empty() {
+                continue;
+            }
+            // In a basic fish, each cover set covers one candidate of a base set.
+            if self.solver.kind == FishKind::Basic
+                && candidates.count() > self.solver.size + self.solver.max_fins
+            {
+                continue;
+            }
+            self.search_base(h + 1, base | (1 << h), base_candidates.union(&candidates));
+        }
+    }
+
+    // Covers the remaining base candidates one by one, either by one of its
+    // houses or by making it a fin.
+    fn search_cover(
+        &mut self,
+        base: u32,
+        base_candidates: NodeBits,
+        remaining: NodeBits,
+        cover: u32,
+        fins: usize,
+    ) {
+        if cover.count_ones() as usize == self.solver.size {
+            // All the remaining candidates have to be fins.
+            if fins + remaining.count() <= self.solver.max_fins {
+                self.check_fish(base, base_candidates, cover);
+            }
+            return;
+        }
+        let idx = match remaining.first() {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        for h in get_houses_for_idx(idx) {
+            if (1 << h) & (self.cover_houses_allowed & !base) == 0
+                || !self.house_candidates[h].has(idx)
+            {
+                continue;
+            }
+            self.search_cover(
+                base,
+                base_candidates,
+                remaining.minus(&self.house_candidates[h]),
+                cover | (1 << h),
+                fins,
+            );
+        }
+
+        if fins < self.solver.max_fins {
+            let mut remaining = remaining;
+            remaining.del(idx);
+            self.search_cover(base, base_candidates, remaining, cover, fins + 1);
+        }
+    }
+
+    fn check_fish(&mut self, base: u32, base_candidates: NodeBits, cover: u32) {
+        if FishKind::classify(base, cover) != self.solver.kind {
+            return;
+        }
+        if self.fishes.iter().any(|f| {
+            to_house_mask(&f.base_houses) == base && to_house_mask(&f.cover_houses) == cover
+        }) {
+            return;
+        }
+
+        let mut cover_candidates = NodeBits::new();
+        for h in 0..HOUSE_COUNT {
+            if (1 << h) & cover != 0 {
+                cover_candidates = cover_candidates.union(&self.house_candidates[h]);
+            }
+        }
+
+        let fins = base_candidates.minus(&cover_candidates);
+        let mut eliminations = cover_candidates.minus(&base_candidates);
+        for f in fins.iter() {
+            eliminations = eliminations.intersect(&NEIGHBOR_BITS_MAP[f]);
+        }
+        if eliminations.is_empty() {
+            return;
+        }
+
+        self.fishes.push(Fish {
+            color: self.color,
+            kind: self.solver.kind,
+            base_houses: from_house_mask(base),
+            cover_houses: from_house_mask(cover),
+            fins: fins.iter().collect(),
+            eliminations: eliminations.iter().collect(),
+        });
+    }
+}
+
+fn to_house_mask(houses: &[usize]) -> u32 {
+    houses.iter().fold(0, |acc, h| acc | (1 << h))
+}
+
+fn from_house_mask(mask: u32) -> Vec<usize> {
+    (0..HOUSE_COUNT).filter(|h| (1 << h) & mask != 0).collect()
+}
+
+impl FishEliminator {
+    pub fn find_fishes(&self, node_arr: &NodeArray) -> Vec<Fish> {
+        let mut ret = Vec::new();
+        for color in 1..=COLOR_COUNT as ColorType {
+            let candidates = get_nodes_with_available_color(node_arr, color);
+            let mut house_candidates = [NodeBits::new(); HOUSE_COUNT];
+            for (h, house_candidate) in house_candidates.iter_mut().enumerate() {
+                let idx_arr = get_all_idx_for_house(h);
+                if idx_arr.iter().any(|&idx| node_arr[idx].color == color) {
+                    continue;
+                }
+                *house_candidate = candidates.intersect(&HOUSE_BITS_ARR[h]);
+            }
+
+            let mut search = FishSearch {
+                solver: self,
+                color,
+                house_candidates,
+                base_houses_allowed: 0,
+                cover_houses_allowed: 0,
+                budget: self.budget,
+                fishes: Vec::new(),
+            };
+            for &(base_houses, cover_houses) in self.kind.house_sets() {
+                search.base_houses_allowed = base_houses;
+                search.cover_houses_allowed = cover_houses;
+                search.search_base(0, 0, NodeBits::new());
+            }
+            ret.append(&mut search.fishes);
+        }
+        ret
+    }
+}
+
+impl StrategySolver for FishEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        self.find_fishes(node_arr)
+            .iter()
+            .map(|f| f.to_step(node_arr))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn fish_eliminator(size: usize, max_fins: usize, kind: FishKind) -> FishEliminator {
+        FishEliminator {
+            size,
+            max_fins,
+            kind,
+            budget: 100000,
+        }
+    }
+
+    // Keeps color 1 only at the given columns in the row.
+    fn keep_in_row(node_arr: &mut NodeArray, row: usize, cols: &[usize]) {
+        for (col, idx) in get_all_idx_for_row(row).into_iter().enumerate() {
+            if !cols.contains(&col) {
+                node_arr[idx].available_colors.del(1);
+            }
+        }
+    }
+
+    #[test]
+    fn x_wing() {
+        let mut node_arr = NodeArray::from_color_array(&[0u8; NODE_COUNT]);
+        keep_in_row(&mut node_arr, 0, &[2, 6]);
+        keep_in_row(&mut node_arr, 4, &[2, 6]);
+
+        let solver = fish_eliminator(2, 0, FishKind::Basic);
+        let fishes = solver.find_fishes(&node_arr);
+        assert_eq!(fishes.len(), 1);
+        assert_eq!(fishes[0].base_houses, vec![0, 4]);
+        assert_eq!(fishes[0].cover_houses, vec![11, 15]);
+        assert_eq!(solver.work(&mut node_arr), 14);
+        for row in [1, 2, 3, 5, 6, 7, 8] {
+            assert!(!node_arr[row * COLOR_COUNT + 2].available_colors.has(1));
+            assert!(!node_arr[row * COLOR_COUNT + 6].available_colors.has(1));
+        }
+        assert!(node_arr[4 * COLOR_COUNT + 6].available_colors.has(1));
+    }
+
+    #[test]
+    fn finned_x_wing() {
+        let mut node_arr = NodeArray::from_color_array(&[0u8; NODE_COUNT]);
+        keep_in_row(&mut node_arr, 0, &[2, 6, 7]);
+        keep_in_row(&mut node_arr, 4, &[2, 6]);
+
+        assert!(fish_eliminator(2, 0, FishKind::Basic)
+            .find_fishes(&node_arr)
+            .is_empty());
+        let fishes = fish_eliminator(2, 1, FishKind::Basic).find_fishes(&node_arr);
+        assert_eq!(fishes.len(), 1);
+        assert_eq!(fishes[0].fins, vec![7]);
+        assert_eq!(fishes[0].eliminations, vec![15, 24]);
+    }
+
+    #[test]
+    fn franken_x_wing() {
+        let mut node_arr = NodeArray::from_color_array(&[0u8; NODE_COUNT]);
+        keep_in_row(&mut node_arr, 0, &[1, 4]);
+        for idx in get_all_idx_for_sqr(6) {
+            if col_idx(idx) != 1 {
+                node_arr[idx].available_colors.del(1);
+            }
+        }
+
+        assert!(fish_eliminator(2, 0, FishKind::Basic)
+            .find_fishes(&node_arr)
+            .is_empty());
+        let fishes = fish_eliminator(2, 0, FishKind::Franken).find_fishes(&node_arr);
+        // Row 0 and square 6 as the base sets, with r0c4 covered by either
+        // column 4 or square 1. Columns 0 and 2 also form a fish covered by
+        // squares 0 and 3.
+        assert_eq!(fishes.len(), 3);
+        let fish = fishes
+            .iter()
+            .find(|f| f.cover_houses == vec![10, 13])
+            .unwrap();
+        assert_eq!(fish.base_houses, vec![0, 24]);
+        assert!(fish.eliminations.contains(&(4 * COLOR_COUNT + 1)));
+        assert!(fish.eliminations.contains(&(8 * COLOR_COUNT + 4)));
+    }
+
+    #[test]
+    fn no_budget() {
+        let mut node_arr = NodeArray::from_color_array(&[0u8; NODE_COUNT]);
+        keep_in_row(&mut node_arr, 0, &[2, 6]);
+        keep_in_row(&mut node_arr, 4, &[2, 6]);
+
+        let mut solver = fish_eliminator(2, 0, FishKind::Basic);
+        solver.budget = 0;
+        assert!(solver.find_fishes(&node_arr).is_empty());
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        for kind in [FishKind::Basic, FishKind::Franken, FishKind::Mutant] {
+            for size in 2..=3 {
+                check_steps_keep_answer(&fish_eliminator(size, 2, kind));
+            }
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use crate::*;
+use solve_utils::*;
+use std::collections::HashSet;
+use strategy_solver::*;
+
+// Forcing chains assume a candidate is the answer, and follow what it implies
+// with the basic techniques: a placed color is eliminated from the other colors
+// of the node and from the neighbors, a node with a single color left is
+// placed, and a color with a single node left in a house is placed there.
+//
+// - Nishio assumes a candidate and follows its color only. If that leads to a
+//   contradiction, the candidate is eliminated.
+// - Cell forcing assumes each color of a node in turn, and region forcing
+//   assumes each node of a color in a house in turn. Whatever is implied by
+//   every assumption is true. Assumptions leading to contradictions are
+//   impossible, so they are eliminated and ignored when looking for the
+//   common implications.
+//
+// When an implication follows from more than one earlier implication, e.g. a
+// node with three colors is placed after two of them are eliminated, the
+// proof is a net instead of a chain.
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ForcingKind {
+    Cell,
+    Region,
+    Nishio,
+}
+
+// A candidate which is either the answer or impossible.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Fact {
+    pub candidate: Candidate,
+    pub placed: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Implication {
+    pub fact: Fact,
+    // Indices of the implications this one follows from, which are always
+    // earlier in the branch. Empty for the assumption.
+    pub causes: Vec<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ForcingBranch {
+    // The implications needed for the conclusion, starting with the
+    // assumption and ending with the conclusion.
+    pub implications: Vec<Implication>,
+    // Indices of the implications which cannot be true together, if the
+    // assumption leads to a contradiction.
+    pub contradiction: Vec<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ForcingChain {
+    pub technique: Technique,
+    pub conclusion: Fact,
+    // One branch for each assumption.
+    pub branches: Vec<ForcingBranch>,
+    pub eliminations: Vec<Candidate>,
+}
+
+impl ForcingChain {
+    pub fn to_step(&self) -> Step {
+        let mut nodes = Vec::new();
+        for branch in self.branches.iter() {
+            let idx = branch.implications[0].fact.candidate.idx;
+            if !nodes.contains(&idx) {
+                nodes.push(idx);
+            }
+        }
+        Step {
+            technique: self.technique,
+            nodes,
+            eliminations: self.eliminations.clone(),
+        }
+    }
+}
+
+const NO_CAUSE: usize = usize::MAX;
+
+const fn bit(color: ColorType) -> u16 {
+    1 << (color - 1)
+}
+
+// The implications of an assumption.
+struct Propagation {
+    max_depth: usize,
+    // Only the implications of this color are followed if set.
+    color: Option<ColorType>,
+    // Available colors of each node. A placed node has its color only.
+    colors: [u16; NODE_COUNT],
+    placed: [ColorType; NODE_COUNT],
+    // Index of the implication placing each node or eliminating each
+    // candidate, or NO_CAUSE if it does not follow from the assumption.
+    place_causes: [usize; NODE_COUNT],
+    elim_causes: [[usize; COLOR_COUNT]; NODE_COUNT],
+    // Colors placed in each house.
+    house_colors: [u16; HOUSE_COUNT],
+    // The causes of facts[i] are causes[cause_starts[i]..cause_starts[i + 1]],
+    // which saves allocating them one by one.
+    facts: Vec<Fact>,
+    cause_starts: Vec<usize>,
+    causes: Vec<usize>,
+    depths: Vec<usize>,
+    contradiction: Vec<usize>,
+}
+
+impl Propagation {
+    fn new(
+        node_arr: &NodeArray,
+        assumption: Candidate,
+        max_depth: usize,
+        color: Option<ColorType>,
+    ) -> Self {
+        let mut ret = Propagation {
+            max_depth,
+            color,
+            colors: [0; NODE_COUNT],
+            placed: [0; NODE_COUNT],
+            place_causes: [NO_CAUSE; NODE_COUNT],
+            elim_causes: [[NO_CAUSE; COLOR_COUNT]; NODE_COUNT],
+            house_colors: [0; HOUSE_COUNT],
+            facts: Vec::new(),
+            cause_starts: vec![0],
+            causes: Vec::new(),
+            depths: Vec::new(),
+            contradiction: Vec::new(),
+        };
+        for (idx, node) in node_arr.iter().enumerate() {
+            if node.color != 0 {
+                ret.colors[idx] = bit(node.color);
+                ret.placed[idx] = node.color;
+                for house in get_houses_for_idx(idx) {
+                    ret.house_colors[house] |= bit(node.color);
+                }
+            } else {
+                ret.colors[idx] = node.available_colors.bits();
+            }
+        }
+        ret.add(assumption, true, &[]);
+        let mut next = 0;
+        while next < ret.facts.len() && !ret.is_contradiction() {
+            if ret.depths[next] < ret.max_depth {
+                ret.follow(next);
+            }
+            next += 1;
+        }
+        ret
+    }
+
+    fn is_contradiction(&self) -> bool {
+        !self.contradiction.is_empty()
+    }
+
+    fn causes_of(&self, i: usize) -> &[usize] {
+        &self.causes[self.cause_starts[i]..self.cause_starts[i + 1]]
+    }
+
+    // Returns the index of the implication proving the fact.
+    fn cause_of(&self, fact: &Fact) -> usize {
+        let Candidate { idx, color } = fact.candidate;
+        if !fact.placed {
+            self.elim_causes[idx][color as usize - 1]
+        } else if self.placed[idx] == color {
+            self.place_causes[idx]
+        } else {
+            NO_CAUSE
+        }
+    }
+
+    fn add(&mut self, candidate: Candidate, placed: bool, causes: &[usize]) {
+        let Candidate { idx, color } = candidate;
+        let conflict = if placed {
+            if self.placed[idx] == color {
+                return;
+            } else if self.placed[idx] != 0 {
+                Some(self.place_causes[idx])
+            } else if self.colors[idx] & bit(color) == 0 {
+                Some(self.elim_causes[idx][color as usize - 1])
+            } else {
+                None
+            }
+        } else if self.colors[idx] & bit(color) == 0 {
+            return;
+        } else if self.placed[idx] == color {
+            Some(self.place_causes[idx])
+        } else {
+            None
+        };
+
+        let depth = causes
+            .iter()
+            .map(|&i| self.depths[i] + 1)
+            .max()
+            .unwrap_or(0);
+        let cur = self.facts.len();
+        self.facts.push(Fact { candidate, placed });
+        self.causes.extend_from_slice(causes);
+        self.cause_starts.push(self.causes.len());
+        self.depths.push(depth);
+        match conflict {
+            Some(other) => {
+                // A conflict with the puzzle itself, and not an implication of
+                // the assumption, has no cause.
+                self.contradiction = vec![cur];
+                if other != NO_CAUSE {
+                    self.contradiction.insert(0, other);
+                }
+            }
+            None if placed => {
+                self.placed[idx] = color;
+                self.place_causes[idx] = cur;
+                for house in get_houses_for_idx(idx) {
+                    self.house_colors[house] |= bit(color);
+                }
+            }
+            None => {
+                self.colors[idx] &= !bit(color);
+                self.elim_causes[idx][color as usize - 1] = cur;
+            }
+        }
+    }
+
+    fn follow(&mut self, cur: usize) {
+        let Fact { candidate, placed } = self.facts[cur];
+        let Candidate { idx, color } = candidate;
+        if placed {
+            if self.color.is_none() {
+                for other in mask_to_colors(self.colors[idx] & !bit(color)) {
+                    self.add(Candidate { idx, color: other }, false, &[cur]);
+                }
+            }
+            for &neigh in NEIGHBOR_ARRAY_MAP[idx].iter() {
+                if self.colors[neigh] & bit(color) != 0 {
+                    self.add(Candidate { idx: neigh, color }, false, &[cur]);
+                }
+                if self.is_contradiction() {
+                    return;
+                }
+            }
+            return;
+        }
+
+        if self.color.is_none() && self.placed[idx] == 0 && self.colors[idx].count_ones() <= 1 {
+            let causes = self.elim_causes[idx]
+                .iter()
+                .copied()
+                .filter(|&i| i != NO_CAUSE)
+                .collect::<Vec<_>>();
+            match mask_to_colors(self.colors[idx]).next() {
+                None => self.contradiction = causes,
+                Some(color) => self.add(Candidate { idx, color }, true, &causes),
+            }
+            if self.is_contradiction() {
+                return;
+            }
+        }
+        for house in get_houses_for_idx(idx) {
+            if self.house_colors[house] & bit(color) != 0 {
+                continue;
+            }
+            let house_idx_arr = get_all_idx_for_house(house);
+            let mut positions = house_idx_arr
+                .iter()
+                .copied()
+                .filter(|&i| self.colors[i] & bit(color) != 0);
+            let first = positions.next();
+            if first.is_some() && positions.next().is_some() {
+                continue;
+            }
+            let causes = house_idx_arr
+                .iter()
+                .map(|&i| self.elim_causes[i][color as usize - 1])
+                .filter(|&i| i != NO_CAUSE)
+                .collect::<Vec<_>>();
+            match first {
+                None => self.contradiction = causes,
+                Some(i) => self.add(Candidate { idx: i, color }, true, &causes),
+            }
+            if self.is_contradiction() {
+                return;
+            }
+        }
+    }
+
+    // Returns the implications needed for the targets, and whether any of them
+    // follows from more than one implication.
+    fn to_branch(&self, targets: &[usize]) -> (ForcingBranch, bool) {
+        let mut needed = vec![false; self.facts.len()];
+        let mut stack = targets.to_vec();
+        while let Some(i) = stack.pop() {
+            if !needed[i] {
+                needed[i] = true;
+                stack.extend(self.causes_of(i));
+            }
+        }
+
+        let mut new_idx = vec![0; self.facts.len()];
+        let mut implications = Vec::new();
+        let mut net = false;
+        for (i, &fact) in self.facts.iter().enumerate() {
+            if !needed[i] {
+                continue;
+            }
+            let causes = self.causes_of(i);
+            net |= causes.len() > 1;
+            new_idx[i] = implications.len();
+            implications.push(Implication {
+                fact,
+                causes: causes.iter().map(|&c| new_idx[c]).collect(),
+            });
+        }
+        let branch = ForcingBranch {
+            implications,
+            contradiction: self.contradiction.iter().map(|&i| new_idx[i]).collect(),
+        };
+        (branch, net)
+    }
+}
+
+pub struct ForcingEliminator {
+    pub kind: ForcingKind,
+    // Max number of steps from an assumption to an implication.
+    pub max_depth: usize,
+    // Max number of assumptions for cell and region forcing.
+    pub max_width: usize,
+}
+
+impl ForcingEliminator {
+    fn technique(&self, net: bool) -> Technique {
+        Technique::Forcing {
+            kind: self.kind,
+            net,
+        }
+    }
+
+    // Returns the sets of assumptions where one of them must be true.
+    fn assumption_sets(&self, node_arr: &NodeArray) -> Vec<Vec<Candidate>> {
+        let mut ret = Vec::new();
+        match self.kind {
+            ForcingKind::Cell => {
+                for (idx, node) in node_arr.iter().enumerate() {
+                    let colors = node.available_colors.bits();
+                    if node.color == 0 && colors.count_ones() as usize <= self.max_width {
+                        ret.push(
+                            mask_to_colors(colors)
+                                .map(|color| Candidate { idx, color })
+                                .collect(),
+                        );
+                    }
+                }
+            }
+            ForcingKind::Region => {
+                for color in 1..=COLOR_COUNT as ColorType {
+                    let color_nodes = get_nodes_with_available_color(node_arr, color);
+                    for house_bits in HOUSE_BITS_ARR.iter() {
+                        let nodes = color_nodes.intersect(house_bits);
+                        // A single node is a hidden single.
+                        if nodes.count() >= 2 && nodes.count() <= self.max_width {
+                            ret.push(nodes.iter().map(|idx| Candidate { idx, color }).collect());
+                        }
+                    }
+                }
+            }
+            ForcingKind::Nishio => {
+                for (idx, node) in node_arr.iter().enumerate() {
+                    if node.color == 0 {
+                        for color in mask_to_colors(node.available_colors.bits()) {
+                            ret.push(vec![Candidate { idx, color }]);
+                        }
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    fn contradiction_chain(&self, propagation: &Propagation) -> ForcingChain {
+        let (branch, net) = propagation.to_branch(&propagation.contradiction);
+        let candidate = branch.implications[0].fact.candidate;
+        ForcingChain {
+            technique: self.technique(net),
+            conclusion: Fact {
+                candidate,
+                placed: false,
+            },
+            branches: vec![branch],
+            eliminations: vec![candidate],
+        }
+    }
+
+    // Returns the facts implied by every branch which is not a contradiction.
+    fn common_chains(
+        &self,
+        node_arr: &NodeArray,
+        propagations: &[Propagation],
+        chains: &mut Vec<ForcingChain>,
+    ) {
+        let valid = propagations
+            .iter()
+            .filter(|p| !p.is_contradiction())
+            .collect::<Vec<_>>();
+        if valid.is_empty() {
+            // The puzzle has no answer.
+            return;
+        }
+        for &fact in valid[0].facts.iter().skip(1) {
+            if valid.iter().any(|p| p.cause_of(&fact) == NO_CAUSE) {
+                continue;
+            }
+            let Candidate { idx, color } = fact.candidate;
+            let eliminations = if fact.placed {
+                mask_to_colors(node_arr[idx].available_colors.bits() & !bit(color))
+                    .map(|color| Candidate { idx, color })
+                    .collect::<Vec<_>>()
+            } else {
+                vec![fact.candidate]
+            };
+            if eliminations.is_empty() {
+                continue;
+            }
+            let mut net = false;
+            let mut branches = Vec::new();
+            for p in propagations.iter() {
+                let targets = if p.is_contradiction() {
+                    p.contradiction.clone()
+                } else {
+                    vec![p.cause_of(&fact)]
+                };
+                let (branch, branch_net) = p.to_branch(&targets);
+                net |= branch_net;
+                branches.push(branch);
+            }
+            chains.push(ForcingChain {
+                technique: self.technique(net),
+                conclusion: fact,
+                branches,
+                eliminations,
+            });
+        }
+    }
+
+    pub fn find_forcing_chains(&self, node_arr: &NodeArray) -> Vec<ForcingChain> {
+        let mut chains = Vec::new();
+        for assumptions in self.assumption_sets(node_arr) {
+            let color = match self.kind {
+                ForcingKind::Nishio => Some(assumptions[0].color),
+                _ => None,
+            };
+            let propagations = assumptions
+                .iter()
+                .map(|&a| Propagation::new(node_arr, a, self.max_depth, color))
+                .collect::<Vec<_>>();
+            for p in propagations.iter().filter(|p| p.is_contradiction()) {
+                chains.push(self.contradiction_chain(p));
+            }
+            if self.kind != ForcingKind::Nishio {
+                self.common_chains(node_arr, &propagations, &mut chains);
+            }
+        }
+
+        chains.sort_by_key(|c| {
+            c.branches
+                .iter()
+                .map(|b| b.implications.len())
+                .sum::<usize>()
+        });
+        let mut eliminated = HashSet::new();
+        chains.retain(|c| {
+            let mut has_new = false;
+            for &e in c.eliminations.iter() {
+                has_new |= eliminated.insert(e);
+            }
+            has_new
+        });
+        chains
+    }
+}
+
+impl StrategySolver for ForcingEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        self.find_forcing_chains(node_arr)
+            .iter()
+            .map(|c| c.to_step())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn eliminator(kind: ForcingKind) -> ForcingEliminator {
+        ForcingEliminator {
+            kind,
+            max_depth: 20,
+            max_width: 4,
+        }
+    }
+
+    // Checks the causes of every implication come before it, and the branch
+    // starts with the assumption.
+    fn check_branch(branch: &ForcingBranch) {
+        assert!(branch.implications[0].causes.is_empty());
+        assert!(branch.implications[0].fact.placed);
+        for (i, implication) in branch.implications.iter().enumerate().skip(1) {
+            assert!(!implication.causes.is_empty());
+            assert!(implication.causes.iter().all(|&c| c < i));
+        }
+    }
+
+    #[test]
+    fn nishio() {
+        // Color 1 is only available at r0c0, r0c1, r1c0, r1c1, r2c2, r3c0 and
+        // r3c1 in the left stack. Placing it at r2c2 leaves r3c0 for it in
+        // column 0 and r3c1 in column 1, which are in the same row. Finding
+        // each of them needs two eliminations, so it is a net.
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        for (idx, node) in node_arr.iter_mut().enumerate() {
+            if col_idx(idx) < 3 && ![0, 1, 9, 10, 20, 27, 28].contains(&idx) {
+                node.available_colors.del(1);
+            }
+        }
+        let chains = eliminator(ForcingKind::Nishio).find_forcing_chains(&node_arr);
+        let chain = chains
+            .iter()
+            .find(|c| c.eliminations == vec![Candidate { idx: 20, color: 1 }])
+            .unwrap();
+        assert_eq!(
+            chain.technique,
+            Technique::Forcing {
+                kind: ForcingKind::Nishio,
+                net: true
+            }
+        );
+        let branch = &chain.branches[0];
+        check_branch(branch);
+        assert!(!branch.contradiction.is_empty());
+    }
+
+    #[test]
+    fn cell_forcing() {
+        // r0c0 {1, 2}: 1 at r0c0 eliminates 1 from r0c4 {1, 3}, placing 3
+        // there, which eliminates 3 from r4c4. 2 at r0c0 eliminates 2 from
+        // r4c0 {2, 3}, placing 3 there, which also eliminates 3 from r4c4.
+        let node_arr = node_arr_with_candidates(&[(0, &[1, 2]), (4, &[1, 3]), (36, &[2, 3])]);
+        let chains = eliminator(ForcingKind::Cell).find_forcing_chains(&node_arr);
+        let target = Candidate { idx: 40, color: 3 };
+        let chain = chains
+            .iter()
+            .find(|c| c.conclusion.candidate == target && c.branches.len() == 2)
+            .unwrap();
+        assert!(!chain.conclusion.placed);
+        for branch in chain.branches.iter() {
+            check_branch(branch);
+            assert!(branch.contradiction.is_empty());
+            assert_eq!(branch.implications.last().unwrap().fact, chain.conclusion);
+        }
+    }
+
+    #[test]
+    fn region_forcing() {
+        // Color 1 of row 0 is at r0c0 or r0c4. 1 at r0c0 eliminates 1 from
+        // r4c0 {1, 2}, placing 2 there, which eliminates 2 from r4c8. 1 at
+        // r0c4 eliminates 1 from r4c4 {1, 2}, placing 2 there, which also
+        // eliminates 2 from r4c8.
+        let mut node_arr = node_arr_with_candidates(&[(36, &[1, 2]), (40, &[1, 2])]);
+        for idx in [1, 2, 3, 5, 6, 7, 8] {
+            node_arr[idx].available_colors.del(1);
+        }
+        let chains = eliminator(ForcingKind::Region).find_forcing_chains(&node_arr);
+        let target = Candidate { idx: 44, color: 2 };
+        let chain = chains
+            .iter()
+            .find(|c| c.eliminations == vec![target])
+            .unwrap();
+        let assumptions = chain
+            .branches
+            .iter()
+            .map(|b| b.implications[0].fact.candidate)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            assumptions,
+            vec![
+                Candidate { idx: 0, color: 1 },
+                Candidate { idx: 4, color: 1 }
+            ]
+        );
+        for branch in chain.branches.iter() {
+            check_branch(branch);
+            assert_eq!(branch.implications.len(), 4);
+            assert_eq!(branch.implications.last().unwrap().fact, chain.conclusion);
+        }
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        for kind in [ForcingKind::Cell, ForcingKind::Region, ForcingKind::Nishio] {
+            check_steps_keep_answer(&eliminator(kind));
+        }
+    }
+}
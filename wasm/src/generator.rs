@@ -4,32 +4,156 @@ use std::{
 };
 use web_time::Instant;
 
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 
 use crate::*;
 use fast_solver::*;
 
-#[derive(Clone, Copy)]
+// Symmetry of the positions of the clues.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Symmetry {
+    #[default]
+    None,
+    Rotational180,
+    Rotational90,
+    // Mirrored by the horizontal axis, i.e. row r and row 8 - r.
+    MirrorHorizontal,
+    // Mirrored by the vertical axis, i.e. column c and column 8 - c.
+    MirrorVertical,
+    // Mirrored by the main diagonal.
+    Diagonal,
+}
+
+impl Symmetry {
+    fn transform(&self, idx: usize) -> usize {
+        let (r, c) = (row_idx(idx), col_idx(idx));
+        let last = COLOR_COUNT - 1;
+        let (r, c) = match self {
+            Symmetry::None => (r, c),
+            Symmetry::Rotational180 => (last - r, last - c),
+            Symmetry::Rotational90 => (c, last - r),
+            Symmetry::MirrorHorizontal => (last - r, c),
+            Symmetry::MirrorVertical => (r, last - c),
+            Symmetry::Diagonal => (c, r),
+        };
+        r * COLOR_COUNT + c
+    }
+
+    // The nodes which must be removed together with the node, including it.
+    pub fn orbit(&self, idx: usize) -> Vec<usize> {
+        let mut ret = vec![idx];
+        let mut next = self.transform(idx);
+        while next != idx {
+            ret.push(next);
+            next = self.transform(next);
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Default)]
 pub struct GeneratorConfig {
     pub timeout: Option<Duration>,
     pub target_clues_num: NodeIndexType,
+    // The same seed and config always generate the same puzzle. With a seed,
+    // the timeout is turned into a number of tries, so the generation may take
+    // more or less time than the timeout. A random seed is used if not set.
+    pub seed: Option<u64>,
+    pub symmetry: Symmetry,
+    // Removes the clues left by the target number of clues or the timeout,
+    // as long as the answer is unique. See make_minimal. With a symmetry, the
+    // puzzle is only minimal by orbits, so is_minimal may not hold.
+    pub minimal: bool,
+}
+
+impl GeneratorConfig {
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+// Limits the tries of a generation loop by the timeout of the config. Since
+// the time depends on the machine, the loop stops after a number of tries
+// instead if a seed is set, estimated from the timeout and the tries made in a
+// second.
+pub(crate) struct TryBudget {
+    start: Instant,
+    timeout: Option<Duration>,
+    max_tries: Option<usize>,
+    tries: usize,
+}
+
+impl TryBudget {
+    pub(crate) fn new(config: &GeneratorConfig, tries_per_sec: f64) -> Self {
+        let max_tries = match (config.seed, config.timeout) {
+            (Some(_), Some(timeout)) => {
+                Some(max((timeout.as_secs_f64() * tries_per_sec) as usize, 1))
+            }
+            _ => None,
+        };
+        Self {
+            start: Instant::now(),
+            timeout: config.timeout,
+            max_tries,
+            tries: 0,
+        }
+    }
+
+    // Counts a try.
+    pub(crate) fn add_try(&mut self) {
+        self.tries += 1;
+    }
+
+    // The number of tries with a seed.
+    pub(crate) fn max_tries(&self) -> Option<usize> {
+        self.max_tries
+    }
+
+    pub(crate) fn tries(&self) -> usize {
+        self.tries
+    }
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        match (self.max_tries, self.timeout) {
+            (Some(max_tries), _) => self.tries >= max_tries,
+            (None, Some(timeout)) => self.start.elapsed() > timeout,
+            (None, None) => false,
+        }
+    }
+
+    // The timeout of a generation nested in a try. With a seed, each try gets
+    // the same share of the timeout, so the nested generation is limited by a
+    // number of tries as well.
+    pub(crate) fn nested_timeout(&self) -> Option<Duration> {
+        let timeout = self.timeout?;
+        Some(match self.max_tries {
+            Some(max_tries) => timeout / max_tries as u32,
+            None => timeout.saturating_sub(self.start.elapsed()),
+        })
+    }
 }
 
-fn shuffle_colors() -> [ColorType; COLOR_COUNT] {
+// Tries of generate_puzzle_from_answer made in a second, about.
+const ANSWER_TRIES_PER_SEC: f64 = 10.0;
+
+fn shuffle_colors(rng: &mut impl Rng) -> [ColorType; COLOR_COUNT] {
     let mut ret = [1, 2, 3, 4, 5, 6, 7, 8, 9];
-    ret.shuffle(&mut thread_rng());
+    ret.shuffle(rng);
     ret
 }
 
-fn generate_full_impl(arr: &mut ColorArray, i: usize) -> bool {
+fn generate_full_impl(arr: &mut ColorArray, i: usize, rng: &mut impl Rng) -> bool {
     if i == NODE_COUNT {
         return true;
     }
 
-    let colors = shuffle_colors();
+    let colors = shuffle_colors(rng);
     for c in colors {
         arr[i] = c;
-        if arr.validate_color_at_idx(c, i) && generate_full_impl(arr, i + 1) {
+        if arr.validate_color_at_idx(c, i) && generate_full_impl(arr, i + 1, rng) {
             return true;
         }
         arr[i] = 0;
@@ -38,12 +162,12 @@ fn generate_full_impl(arr: &mut ColorArray, i: usize) -> bool {
 }
 
 // Generates a full sudoku array (no empty cells) randomly.
-pub fn generate_answer() -> ColorArray {
+pub fn generate_answer_with_rng(rng: &mut impl Rng) -> ColorArray {
     let mut arr = [0; NODE_COUNT];
 
     // Generate the first row directly.
-    arr[..COLOR_COUNT].copy_from_slice(&shuffle_colors());
-    generate_full_impl(&mut arr, COLOR_COUNT);
+    arr[..COLOR_COUNT].copy_from_slice(&shuffle_colors(rng));
+    generate_full_impl(&mut arr, COLOR_COUNT, rng);
     if !arr.validate_colors(true) {
         panic!(
             "Failed to generate full array: {}",
@@ -67,14 +191,14 @@ const fn create_nodes_array() -> [u8; NODE_COUNT] {
 const NODES_ARRAY: [u8; NODE_COUNT] = create_nodes_array();
 
 // Shuffles the nodes index randomly.
-fn shuffle_nodes() -> [u8; NODE_COUNT] {
+fn shuffle_nodes(rng: &mut impl Rng) -> [u8; NODE_COUNT] {
     let mut ret = NODES_ARRAY;
-    ret.shuffle(&mut thread_rng());
+    ret.shuffle(rng);
     ret
 }
 
-fn nodes_sorted_by_connected_zeros(arr: &ColorArray) -> Vec<u8> {
-    let nodes_to_try = shuffle_nodes();
+fn nodes_sorted_by_connected_zeros(arr: &ColorArray, rng: &mut impl Rng) -> Vec<u8> {
+    let nodes_to_try = shuffle_nodes(rng);
     let count_zero_in_neighs = |i: u8| {
         let mut cnt = 0;
         for j in NEIGHBOR_ARRAY_MAP[i as usize] {
@@ -95,13 +219,13 @@ fn nodes_sorted_by_connected_zeros(arr: &ColorArray) -> Vec<u8> {
 }
 
 #[allow(dead_code)]
-fn nodes_sorted_by_colors_num(arr: &ColorArray) -> Vec<u8> {
+fn nodes_sorted_by_colors_num(arr: &ColorArray, rng: &mut impl Rng) -> Vec<u8> {
     let mut colors = [0; COLOR_COUNT + 1];
     for i in 0..arr.len() {
         colors[arr[i] as usize] += 1;
     }
 
-    let nodes_to_try = shuffle_nodes();
+    let nodes_to_try = shuffle_nodes(rng);
     // Try sort the nodes by the number of 0 value it connects to.
     let mut pairs = nodes_to_try
         .iter()
@@ -143,6 +267,7 @@ fn generate_puzzle_from_answer_dfs(
     config: GeneratorConfig,
     cannot_remove: &[bool; NODE_COUNT],
     tmp_result: &mut IntermediateResult,
+    rng: &mut impl Rng,
 ) -> bool {
     match check_puzzle_has_unique_answer(arr, answer) {
         true => tmp_result.update_puzzle(arr),
@@ -155,52 +280,94 @@ fn generate_puzzle_from_answer_dfs(
     }
 
     let mut cannot_remove_copy = *cannot_remove;
-    for i in nodes_sorted_by_connected_zeros(arr) {
-        if cannot_remove[i as usize] {
+    for i in nodes_sorted_by_connected_zeros(arr, rng) {
+        if cannot_remove_copy[i as usize] {
             continue;
         }
         if arr[i as usize] == 0 {
             continue;
         }
-        let val = arr[i as usize];
-        arr[i as usize] = 0;
-        if generate_puzzle_from_answer_dfs(answer, arr, config, &cannot_remove_copy, tmp_result) {
+        // The clues of the orbit are removed together to keep the symmetry.
+        // An orbit which failed is marked, so its other nodes are skipped.
+        let orbit = config.symmetry.orbit(i as usize);
+        if orbit.iter().any(|&j| cannot_remove_copy[j]) {
+            continue;
+        }
+        for &j in orbit.iter() {
+            arr[j] = 0;
+        }
+        if generate_puzzle_from_answer_dfs(
+            answer,
+            arr,
+            config,
+            &cannot_remove_copy,
+            tmp_result,
+            rng,
+        ) {
             return true;
         }
-        arr[i as usize] = val;
-        cannot_remove_copy[i as usize] = true;
+        for &j in orbit.iter() {
+            arr[j] = answer[j];
+            cannot_remove_copy[j] = true;
+        }
     }
 
     false
 }
 
-fn drop_numbers_uniformly(answer: &ColorArray, target_clues_num: NodeIndexType) -> ColorArray {
+fn drop_numbers_uniformly(
+    answer: &ColorArray,
+    target_clues_num: NodeIndexType,
+    symmetry: Symmetry,
+    rng: &mut impl Rng,
+) -> ColorArray {
     let mut pos = [[0u8; COLOR_COUNT]; COLOR_COUNT];
     let mut cnt = [0_u8; COLOR_COUNT];
-    for i in 0..NODE_COUNT {
-        let c = answer[i];
+    for (i, &c) in answer.iter().enumerate() {
         pos[c as usize - 1][cnt[c as usize - 1] as usize] = i as u8;
         cnt[c as usize - 1] += 1;
     }
-    for i in 0..COLOR_COUNT {
-        pos[i].shuffle(&mut thread_rng())
+    for color_pos in pos.iter_mut() {
+        color_pos.shuffle(rng)
     }
     let mut ret = *answer;
 
     // Leave 81-6*9=27 at least. Leaving 21 numbers is very inefficient.
     let steps = min((NODE_COUNT - target_clues_num) / COLOR_COUNT, 6);
-    for c in 0..COLOR_COUNT {
-        for j in 0..steps {
-            let p = pos[c][j];
-            ret[p as usize] = 0;
+    if symmetry == Symmetry::None {
+        for color_pos in pos.iter() {
+            for &p in color_pos[..steps].iter() {
+                ret[p as usize] = 0;
+            }
+        }
+        return ret;
+    }
+
+    // Remove the orbits of the positions in turns of the colors, as long as
+    // the same number of clues is not exceeded.
+    let mut cnt = steps * COLOR_COUNT;
+    for step in 0..steps {
+        for color_pos in pos.iter() {
+            let orbit = symmetry.orbit(color_pos[step] as usize);
+            if ret[orbit[0]] == 0 || orbit.len() > cnt {
+                continue;
+            }
+            for &p in orbit.iter() {
+                ret[p] = 0;
+            }
+            cnt -= orbit.len();
         }
     }
     ret
 }
 
-fn generate_puzzle_by_random_sequence(answer: &ColorArray, config: GeneratorConfig) -> ColorArray {
+fn generate_puzzle_by_random_sequence(
+    answer: &ColorArray,
+    config: GeneratorConfig,
+    rng: &mut impl Rng,
+) -> ColorArray {
     loop {
-        let puzzle = drop_numbers_uniformly(answer, config.target_clues_num);
+        let puzzle = drop_numbers_uniformly(answer, config.target_clues_num, config.symmetry, rng);
         match check_puzzle_has_unique_answer(&puzzle, answer) {
             true => return puzzle,
             false => continue,
@@ -209,27 +376,32 @@ fn generate_puzzle_by_random_sequence(answer: &ColorArray, config: GeneratorConf
 }
 
 fn generate_puzzle_from_answer_impl(answer: &ColorArray, config: GeneratorConfig) -> ColorArray {
-    let now = Instant::now();
+    let mut budget = TryBudget::new(&config, ANSWER_TRIES_PER_SEC);
+    let mut rng = config.rng();
     let mut tmp_result = IntermediateResult::new();
     let mut loop_cnt = 0;
     loop {
         loop_cnt += 1;
-        if let Some(timeout) = config.timeout {
-            if now.elapsed() > timeout {
+        // At least one puzzle is tried, even if the timeout is too short.
+        if let Some(puzzle) = tmp_result.best_puzzle {
+            if budget.is_exhausted() {
                 println!(
                     "Found suboptimal result with clue cnt: {}, loop cnt: {}",
                     tmp_result.best_hint_cnt, loop_cnt
                 );
-                return tmp_result.best_puzzle.unwrap();
+                return puzzle;
             }
         }
+        budget.add_try();
 
         let mut puzzle = generate_puzzle_by_random_sequence(
             answer,
             GeneratorConfig {
                 timeout: None,
                 target_clues_num: max(config.target_clues_num, 27),
+                ..config
             },
+            &mut rng,
         );
 
         if !generate_puzzle_from_answer_dfs(
@@ -238,6 +410,7 @@ fn generate_puzzle_from_answer_impl(answer: &ColorArray, config: GeneratorConfig
             config,
             &[false; NODE_COUNT],
             &mut tmp_result,
+            &mut rng,
         ) {
             continue;
         }
@@ -249,7 +422,10 @@ fn generate_puzzle_from_answer_impl(answer: &ColorArray, config: GeneratorConfig
 // target_non_empty controls the minimum number of the non-empty cells in the puzzle.
 // It should not be smaller than 17.
 pub fn generate_puzzle_from_answer(answer: &ColorArray, config: GeneratorConfig) -> ColorArray {
-    let puzzle = generate_puzzle_from_answer_impl(answer, config);
+    let mut puzzle = generate_puzzle_from_answer_impl(answer, config);
+    if config.minimal {
+        puzzle = make_minimal_impl(&puzzle, answer, config.symmetry);
+    }
 
     // Validate the puzzle again.
     match fast_solver::solve(&puzzle) {
@@ -263,43 +439,182 @@ pub fn generate_puzzle_from_answer(answer: &ColorArray, config: GeneratorConfig)
     puzzle
 }
 
+// Whether the puzzle has a unique answer, and removing any clue breaks it.
+pub fn is_minimal(puzzle: &ColorArray) -> bool {
+    let SolveResult::Unique(answer) = solve(puzzle) else {
+        return false;
+    };
+    let mut arr = *puzzle;
+    for idx in 0..NODE_COUNT {
+        if puzzle[idx] == 0 {
+            continue;
+        }
+        arr[idx] = 0;
+        if check_puzzle_has_unique_answer(&arr, &answer) {
+            return false;
+        }
+        arr[idx] = puzzle[idx];
+    }
+    true
+}
+
+// Removing a clue only adds answers, so a clue which cannot be removed stays
+// so after the others are removed, and a single pass is enough.
+fn make_minimal_impl(puzzle: &ColorArray, answer: &ColorArray, symmetry: Symmetry) -> ColorArray {
+    let mut ret = *puzzle;
+    for idx in 0..NODE_COUNT {
+        let orbit = symmetry.orbit(idx);
+        if orbit.iter().any(|&j| ret[j] == 0) {
+            continue;
+        }
+        for &j in orbit.iter() {
+            ret[j] = 0;
+        }
+        if !check_puzzle_has_unique_answer(&ret, answer) {
+            for &j in orbit.iter() {
+                ret[j] = answer[j];
+            }
+        }
+    }
+    ret
+}
+
+// Removes the clues of the puzzle in order, as long as the answer is unique.
+// With a symmetry, the clues of an orbit are removed together, and only the
+// orbits whose clues are all given. The result is then minimal by orbits, but
+// a single clue may still be removable. Returns None if the puzzle does not
+// have a unique answer.
+pub fn make_minimal(puzzle: &ColorArray, symmetry: Symmetry) -> Option<ColorArray> {
+    match solve(puzzle) {
+        SolveResult::Unique(answer) => Some(make_minimal_impl(puzzle, &answer, symmetry)),
+        _ => None,
+    }
+}
+
 pub fn generate_puzzle(config: GeneratorConfig) -> ColorArray {
-    let arr = generate_answer();
+    let arr = generate_answer_with_rng(&mut config.rng());
     generate_puzzle_from_answer(&arr, config)
 }
 
+// Number of solver calls before restarting the search from a mask with a new
+// random order.
+const MASK_SEARCH_BUDGET: usize = 2000;
+
+// Number of restarts of the search from a mask when there is no timeout.
+const MAX_MASK_RESTARTS: usize = 30;
+
+// Restarts of the search from a mask made in a second, about.
+const MASK_TRIES_PER_SEC: f64 = 20.0;
+
+// No sudoku with less than 17 clues has a unique answer.
+const MIN_CLUES_NUM: usize = 17;
+
+// Assigns the colors of the cells in order, as long as the puzzle has an
+// answer. Once the answer is unique, the rest of the cells are filled by it.
+fn generate_puzzle_from_mask_dfs(
+    puzzle: &mut ColorArray,
+    cells: &[usize],
+    budget: &mut usize,
+    rng: &mut impl Rng,
+) -> Option<ColorArray> {
+    if *budget == 0 {
+        return None;
+    }
+    *budget -= 1;
+    match solve(puzzle) {
+        SolveResult::Invalid => return None,
+        SolveResult::Unique(answer) => {
+            let mut ret = *puzzle;
+            for &idx in cells {
+                ret[idx] = answer[idx];
+            }
+            return Some(ret);
+        }
+        SolveResult::Multiple => {}
+    }
+
+    let (&idx, rest) = cells.split_first()?;
+    for c in shuffle_colors(rng) {
+        if !puzzle.validate_color_at_idx(c, idx) {
+            continue;
+        }
+        puzzle[idx] = c;
+        if let Some(ret) = generate_puzzle_from_mask_dfs(puzzle, rest, budget, rng) {
+            return Some(ret);
+        }
+    }
+    puzzle[idx] = 0;
+    None
+}
+
+// Generates a puzzle whose clues are exactly at the nodes of the mask, by
+// searching the answer and the colors of the clues together. Returns None if
+// the mask has less than 17 nodes, or if no puzzle is found before the
+// timeout, or after MAX_MASK_RESTARTS restarts without a timeout.
+// The target number of clues and the symmetry of the config are not used.
+pub fn generate_puzzle_from_mask(
+    mask: &[bool; NODE_COUNT],
+    config: GeneratorConfig,
+) -> Option<ColorArray> {
+    let mut rng = config.rng();
+    let mut cells = (0..NODE_COUNT).filter(|&idx| mask[idx]).collect::<Vec<_>>();
+    if cells.len() < MIN_CLUES_NUM {
+        return None;
+    }
+    let mut restarts = TryBudget::new(&config, MASK_TRIES_PER_SEC);
+    loop {
+        if restarts.is_exhausted()
+            || (config.timeout.is_none() && restarts.tries() >= MAX_MASK_RESTARTS)
+        {
+            return None;
+        }
+        restarts.add_try();
+        cells.shuffle(&mut rng);
+        let mut puzzle = [0; NODE_COUNT];
+        let mut budget = MASK_SEARCH_BUDGET;
+        if let Some(puzzle) =
+            generate_puzzle_from_mask_dfs(&mut puzzle, &cells, &mut budget, &mut rng)
+        {
+            return Some(puzzle);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::thread_rng;
 
     #[test]
     fn test_generate_answer() {
-        let answer = generate_answer();
+        let answer = generate_answer_with_rng(&mut thread_rng());
         assert!(answer.validate_colors(true));
     }
 
     #[test]
     fn test_generate_puzzle_by_random_sequence_28() {
-        let answer = generate_answer();
+        let answer = generate_answer_with_rng(&mut thread_rng());
         let puzzle = generate_puzzle_by_random_sequence(
             &answer,
             GeneratorConfig {
-                timeout: None,
                 target_clues_num: 28,
+                ..Default::default()
             },
+            &mut thread_rng(),
         );
         assert_eq!(puzzle.count_clues(), 36);
     }
 
     #[test]
     fn test_generate_puzzle_by_random_sequence_27() {
-        let answer = generate_answer();
+        let answer = generate_answer_with_rng(&mut thread_rng());
         let puzzle = generate_puzzle_by_random_sequence(
             &answer,
             GeneratorConfig {
-                timeout: None,
                 target_clues_num: 27,
+                ..Default::default()
             },
+            &mut thread_rng(),
         );
         assert_eq!(puzzle.count_clues(), 27);
     }
@@ -317,6 +632,7 @@ mod tests {
             GeneratorConfig {
                 timeout: Some(Duration::from_secs(1)),
                 target_clues_num: 17,
+                ..Default::default()
             },
         );
 
@@ -325,16 +641,196 @@ mod tests {
 
     #[test]
     fn test_generate_puzzle() {
-        let answer = generate_answer();
+        let answer = generate_answer_with_rng(&mut thread_rng());
         let puzzle = generate_puzzle_from_answer(
             &answer,
             GeneratorConfig {
                 timeout: Some(Duration::from_secs(1)),
                 target_clues_num: 17,
+                ..Default::default()
             },
         );
         assert!(puzzle.count_clues() < 27);
 
         println!("Score: {}", simple_score(&puzzle));
     }
+
+    #[test]
+    fn same_seed_same_puzzle() {
+        let config = |seed| GeneratorConfig {
+            target_clues_num: 26,
+            seed: Some(seed),
+            ..Default::default()
+        };
+        let puzzle = generate_puzzle(config(42));
+        assert_eq!(generate_puzzle(config(42)), puzzle);
+        assert_ne!(generate_puzzle(config(43)), puzzle);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let answer = generate_answer_with_rng(&mut rng);
+        assert_eq!(
+            generate_answer_with_rng(&mut StdRng::seed_from_u64(7)),
+            answer
+        );
+        assert_eq!(
+            generate_puzzle_from_answer(&answer, config(1)),
+            generate_puzzle_from_answer(&answer, config(1))
+        );
+    }
+
+    #[test]
+    fn same_seed_same_puzzle_by_timeout() {
+        // 17 clues are not reached, so the generation stops by the timeout,
+        // which is a number of tries with a seed.
+        let config = GeneratorConfig {
+            timeout: Some(Duration::from_millis(300)),
+            target_clues_num: 17,
+            seed: Some(5),
+            ..Default::default()
+        };
+        let puzzle = generate_puzzle(config);
+        assert!(puzzle.count_clues() > 17);
+        assert_eq!(generate_puzzle(config), puzzle);
+
+        let config = GeneratorConfig {
+            timeout: Some(Duration::ZERO),
+            ..config
+        };
+        assert_eq!(generate_puzzle(config), generate_puzzle(config));
+    }
+
+    #[test]
+    fn symmetric_puzzles() {
+        for symmetry in [
+            Symmetry::Rotational180,
+            Symmetry::Rotational90,
+            Symmetry::MirrorHorizontal,
+            Symmetry::MirrorVertical,
+            Symmetry::Diagonal,
+        ] {
+            let puzzle = generate_puzzle(GeneratorConfig {
+                target_clues_num: 30,
+                seed: Some(1),
+                symmetry,
+                ..Default::default()
+            });
+            assert!(matches!(solve(&puzzle), SolveResult::Unique(_)));
+            assert!(puzzle.count_clues() <= 30, "{:?}", symmetry);
+            for idx in 0..NODE_COUNT {
+                let clue = puzzle[idx] != 0;
+                assert!(symmetry
+                    .orbit(idx)
+                    .iter()
+                    .all(|&j| (puzzle[j] != 0) == clue));
+            }
+        }
+    }
+
+    #[test]
+    fn minimal_puzzles() {
+        let config = GeneratorConfig {
+            target_clues_num: 32,
+            seed: Some(2),
+            ..Default::default()
+        };
+        let puzzle = generate_puzzle(config);
+        let minimal = make_minimal(&puzzle, Symmetry::None).unwrap();
+        assert!(is_minimal(&minimal));
+        assert_eq!(solve(&minimal), solve(&puzzle));
+        assert!((0..NODE_COUNT).all(|idx| minimal[idx] == 0 || minimal[idx] == puzzle[idx]));
+        if minimal != puzzle {
+            assert!(!is_minimal(&puzzle));
+        }
+        assert_eq!(make_minimal(&[0; NODE_COUNT], Symmetry::None), None);
+        assert!(!is_minimal(&[0; NODE_COUNT]));
+
+        let puzzle = generate_puzzle(GeneratorConfig {
+            minimal: true,
+            ..config
+        });
+        assert!(is_minimal(&puzzle));
+    }
+
+    #[test]
+    fn minimal_keeps_symmetry() {
+        let symmetry = Symmetry::Rotational180;
+        let puzzle = generate_puzzle(GeneratorConfig {
+            target_clues_num: 34,
+            seed: Some(4),
+            symmetry,
+            ..Default::default()
+        });
+        let minimal = make_minimal(&puzzle, symmetry).unwrap();
+        assert!(matches!(solve(&minimal), SolveResult::Unique(_)));
+        for idx in 0..NODE_COUNT {
+            let orbit = symmetry.orbit(idx);
+            assert!(orbit
+                .iter()
+                .all(|&j| (minimal[j] != 0) == (minimal[idx] != 0)));
+            // Removing any orbit breaks the uniqueness.
+            if minimal[idx] != 0 {
+                let mut arr = minimal;
+                for &j in orbit.iter() {
+                    arr[j] = 0;
+                }
+                assert_eq!(solve(&arr), SolveResult::Multiple);
+            }
+        }
+    }
+
+    #[test]
+    fn orbits() {
+        assert_eq!(Symmetry::None.orbit(10), vec![10]);
+        assert_eq!(Symmetry::Rotational180.orbit(40), vec![40]);
+        assert_eq!(Symmetry::Rotational180.orbit(0), vec![0, 80]);
+        assert_eq!(Symmetry::Rotational90.orbit(0), vec![0, 8, 80, 72]);
+        assert_eq!(Symmetry::MirrorHorizontal.orbit(1), vec![1, 73]);
+        assert_eq!(Symmetry::MirrorVertical.orbit(1), vec![1, 7]);
+        assert_eq!(Symmetry::Diagonal.orbit(1), vec![1, 9]);
+        assert_eq!(Symmetry::Diagonal.orbit(10), vec![10]);
+    }
+
+    #[test]
+    fn puzzle_from_mask() {
+        let shape = generate_puzzle(GeneratorConfig {
+            target_clues_num: 28,
+            seed: Some(3),
+            ..Default::default()
+        });
+        let mask = shape.map(|c| c != 0);
+        let config = GeneratorConfig {
+            timeout: Some(Duration::from_secs(10)),
+            seed: Some(5),
+            ..Default::default()
+        };
+        let puzzle = generate_puzzle_from_mask(&mask, config).unwrap();
+        assert!(matches!(solve(&puzzle), SolveResult::Unique(_)));
+        for idx in 0..NODE_COUNT {
+            assert_eq!(puzzle[idx] != 0, mask[idx]);
+        }
+        assert_eq!(generate_puzzle_from_mask(&mask, config), Some(puzzle));
+    }
+
+    #[test]
+    fn puzzle_from_too_small_mask() {
+        let mut mask = [false; NODE_COUNT];
+        mask[..16].fill(true);
+        let config = GeneratorConfig {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        assert_eq!(generate_puzzle_from_mask(&mask, config), None);
+    }
+
+    #[test]
+    fn puzzle_from_mask_without_timeout() {
+        assert_eq!(
+            generate_puzzle_from_mask(&[false; NODE_COUNT], Default::default()),
+            None
+        );
+        // Two full rows leave the other rows free, so no puzzle is unique.
+        let mut mask = [false; NODE_COUNT];
+        mask[..18].fill(true);
+        assert_eq!(generate_puzzle_from_mask(&mask, Default::default()), None);
+    }
 }
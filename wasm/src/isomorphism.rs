@@ -0,0 +1,407 @@
+use crate::*;
+
+// The puzzles which are the same up to relabeling the digits, permuting the
+// rows in a band, the columns in a stack, the bands and the stacks, and
+// transposing, are equivalent. They have the same answers up to the same
+// transformation, and they are solved with the same techniques.
+
+// Maps a puzzle to an equivalent one. The source is transposed first if
+// transpose is set, then row r of the result is row rows[r] of the source,
+// column c is column cols[c], and each digit d becomes digits[d - 1].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Transformation {
+    pub transpose: bool,
+    pub rows: [usize; COLOR_COUNT],
+    pub cols: [usize; COLOR_COUNT],
+    pub digits: [ColorType; COLOR_COUNT],
+}
+
+const IDENTITY: [usize; COLOR_COUNT] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+fn inverse_permutation(perm: &[usize; COLOR_COUNT]) -> [usize; COLOR_COUNT] {
+    let mut ret = [0; COLOR_COUNT];
+    for (i, &p) in perm.iter().enumerate() {
+        ret[p] = i;
+    }
+    ret
+}
+
+// Permutes the lines of a band or a stack.
+fn permute_lines_in_block(block: usize, perm: [usize; RANK]) -> [usize; COLOR_COUNT] {
+    let mut ret = IDENTITY;
+    for i in 0..RANK {
+        ret[block * RANK + i] = block * RANK + perm[i];
+    }
+    ret
+}
+
+// Permutes the bands or the stacks.
+fn permute_blocks(perm: [usize; RANK]) -> [usize; COLOR_COUNT] {
+    let mut ret = IDENTITY;
+    for i in 0..COLOR_COUNT {
+        ret[i] = perm[i / RANK] * RANK + i % RANK;
+    }
+    ret
+}
+
+impl Transformation {
+    pub fn identity() -> Self {
+        Self {
+            transpose: false,
+            rows: IDENTITY,
+            cols: IDENTITY,
+            digits: [1, 2, 3, 4, 5, 6, 7, 8, 9],
+        }
+    }
+
+    pub fn transposition() -> Self {
+        Self {
+            transpose: true,
+            ..Self::identity()
+        }
+    }
+
+    // Digit d becomes digits[d - 1], which must be a permutation of 1 to 9.
+    pub fn relabeling(digits: [ColorType; COLOR_COUNT]) -> Self {
+        Self {
+            digits,
+            ..Self::identity()
+        }
+    }
+
+    // Row i of the band is row perm[i] of the band in the source.
+    pub fn row_permutation(band: usize, perm: [usize; RANK]) -> Self {
+        Self {
+            rows: permute_lines_in_block(band, perm),
+            ..Self::identity()
+        }
+    }
+
+    pub fn column_permutation(stack: usize, perm: [usize; RANK]) -> Self {
+        Self {
+            cols: permute_lines_in_block(stack, perm),
+            ..Self::identity()
+        }
+    }
+
+    // Band i is band perm[i] in the source.
+    pub fn band_permutation(perm: [usize; RANK]) -> Self {
+        Self {
+            rows: permute_blocks(perm),
+            ..Self::identity()
+        }
+    }
+
+    pub fn stack_permutation(perm: [usize; RANK]) -> Self {
+        Self {
+            cols: permute_blocks(perm),
+            ..Self::identity()
+        }
+    }
+
+    pub fn apply(&self, puzzle: &ColorArray) -> ColorArray {
+        let mut ret = [0; NODE_COUNT];
+        for r in 0..COLOR_COUNT {
+            for c in 0..COLOR_COUNT {
+                let (sr, sc) = (self.rows[r], self.cols[c]);
+                let (sr, sc) = if self.transpose { (sc, sr) } else { (sr, sc) };
+                let color = puzzle[sr * COLOR_COUNT + sc];
+                if color != 0 {
+                    ret[r * COLOR_COUNT + c] = self.digits[color as usize - 1];
+                }
+            }
+        }
+        ret
+    }
+
+    // The transformation which applies this one and then the next one.
+    pub fn then(&self, next: &Transformation) -> Self {
+        let (rows, cols) = if next.transpose {
+            (&self.cols, &self.rows)
+        } else {
+            (&self.rows, &self.cols)
+        };
+        Self {
+            transpose: self.transpose != next.transpose,
+            rows: next.rows.map(|r| rows[r]),
+            cols: next.cols.map(|c| cols[c]),
+            digits: self.digits.map(|d| next.digits[d as usize - 1]),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        let rows = inverse_permutation(&self.rows);
+        let cols = inverse_permutation(&self.cols);
+        let mut digits = [0; COLOR_COUNT];
+        for (i, &d) in self.digits.iter().enumerate() {
+            digits[d as usize - 1] = i as ColorType + 1;
+        }
+        let (rows, cols) = if self.transpose {
+            (cols, rows)
+        } else {
+            (rows, cols)
+        };
+        Self {
+            transpose: self.transpose,
+            rows,
+            cols,
+            digits,
+        }
+    }
+}
+
+// A partial canonical form, whose first rows are chosen.
+#[derive(Clone)]
+struct Candidate {
+    transpose: bool,
+    rows: [usize; COLOR_COUNT],
+    cols: [usize; COLOR_COUNT],
+    // The labels of the digits by their first appearance, 0 if not seen yet.
+    labels: [ColorType; COLOR_COUNT],
+    next_label: ColorType,
+}
+
+impl Candidate {
+    // Relabels the source row in the order of the columns. Returns it with the
+    // labels and the next label after it.
+    fn row(
+        &self,
+        puzzle: &ColorArray,
+        source_row: usize,
+    ) -> (
+        [ColorType; COLOR_COUNT],
+        [ColorType; COLOR_COUNT],
+        ColorType,
+    ) {
+        let mut labels = self.labels;
+        let mut next_label = self.next_label;
+        let mut ret = [0; COLOR_COUNT];
+        for (c, &source_col) in self.cols.iter().enumerate() {
+            let idx = match self.transpose {
+                true => source_col * COLOR_COUNT + source_row,
+                false => source_row * COLOR_COUNT + source_col,
+            };
+            let color = puzzle[idx];
+            if color == 0 {
+                continue;
+            }
+            let label = &mut labels[color as usize - 1];
+            if *label == 0 {
+                *label = next_label;
+                next_label += 1;
+            }
+            ret[c] = *label;
+        }
+        (ret, labels, next_label)
+    }
+}
+
+fn block_permutations() -> Vec<[usize; RANK]> {
+    vec![
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ]
+}
+
+// All the column orders made by permuting the stacks and the columns in them.
+fn column_orders() -> Vec<[usize; COLOR_COUNT]> {
+    let perms = block_permutations();
+    let mut ret = Vec::new();
+    for stacks in perms.iter() {
+        for p0 in perms.iter() {
+            for p1 in perms.iter() {
+                for p2 in perms.iter() {
+                    let mut cols = [0; COLOR_COUNT];
+                    for (i, p) in [p0, p1, p2].into_iter().enumerate() {
+                        for j in 0..RANK {
+                            cols[i * RANK + j] = stacks[i] * RANK + p[j];
+                        }
+                    }
+                    ret.push(cols);
+                }
+            }
+        }
+    }
+    ret
+}
+
+// Returns the canonical form of the puzzle, and the transformation which maps
+// the puzzle to it. The equivalent puzzles have the same canonical form.
+//
+// The canonical form is the least equivalent puzzle in the row-major order,
+// where the digits are relabeled by their first appearance, and the empty
+// nodes are less than the clues. Since its first rows are the least of all the
+// equivalent puzzles too, the rows are chosen one by one, keeping only the
+// candidates whose rows are the least so far.
+pub fn canonicalize(puzzle: &ColorArray) -> (ColorArray, Transformation) {
+    // Every transformation ties for the empty puzzle.
+    if puzzle.count_clues() == 0 {
+        return (*puzzle, Transformation::identity());
+    }
+    let mut candidates = Vec::new();
+    for transpose in [false, true] {
+        for cols in column_orders() {
+            candidates.push(Candidate {
+                transpose,
+                rows: IDENTITY,
+                cols,
+                labels: [0; COLOR_COUNT],
+                next_label: 1,
+            });
+        }
+    }
+
+    let mut ret = [0; NODE_COUNT];
+    for depth in 0..COLOR_COUNT {
+        let mut least: Option<[ColorType; COLOR_COUNT]> = None;
+        let mut next_candidates = Vec::new();
+        for candidate in candidates.iter() {
+            // A new band is any band not used yet, otherwise the row is in the
+            // band of the previous row.
+            let used = &candidate.rows[..depth];
+            let source_rows = (0..COLOR_COUNT).filter(|&r| {
+                !used.contains(&r)
+                    && match depth % RANK {
+                        0 => used.iter().all(|&u| u / RANK != r / RANK),
+                        _ => used[depth - 1] / RANK == r / RANK,
+                    }
+            });
+            for source_row in source_rows {
+                let (row, labels, next_label) = candidate.row(puzzle, source_row);
+                if least.is_some_and(|least| row > least) {
+                    continue;
+                }
+                if least.is_none_or(|least| row < least) {
+                    least = Some(row);
+                    next_candidates.clear();
+                }
+                let mut next = candidate.clone();
+                next.rows[depth] = source_row;
+                next.labels = labels;
+                next.next_label = next_label;
+                next_candidates.push(next);
+            }
+        }
+        let start = depth * COLOR_COUNT;
+        ret[start..start + COLOR_COUNT].copy_from_slice(&least.unwrap());
+        candidates = next_candidates;
+    }
+
+    // The digits not in the puzzle are labeled in order.
+    let mut candidate = candidates.swap_remove(0);
+    for label in candidate.labels.iter_mut() {
+        if *label == 0 {
+            *label = candidate.next_label;
+            candidate.next_label += 1;
+        }
+    }
+    let transformation = Transformation {
+        transpose: candidate.transpose,
+        rows: candidate.rows,
+        cols: candidate.cols,
+        digits: candidate.labels,
+    };
+    (ret, transformation)
+}
+
+pub fn is_equivalent(a: &ColorArray, b: &ColorArray) -> bool {
+    canonicalize(a).0 == canonicalize(b).0
+}
+
+// Returns the transformation which maps puzzle a to puzzle b, if they are
+// equivalent.
+pub fn find_transformation(a: &ColorArray, b: &ColorArray) -> Option<Transformation> {
+    let (canonical_a, to_a) = canonicalize(a);
+    let (canonical_b, to_b) = canonicalize(b);
+    (canonical_a == canonical_b).then(|| to_a.then(&to_b.inverse()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use test_utils::*;
+
+    fn random_transformation(rng: &mut impl Rng) -> Transformation {
+        let perms = block_permutations();
+        let mut digits = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        digits.shuffle(rng);
+        let mut ret = Transformation::relabeling(digits);
+        for i in 0..RANK {
+            ret = ret
+                .then(&Transformation::row_permutation(
+                    i,
+                    *perms.choose(rng).unwrap(),
+                ))
+                .then(&Transformation::column_permutation(
+                    i,
+                    *perms.choose(rng).unwrap(),
+                ));
+        }
+        ret = ret
+            .then(&Transformation::band_permutation(
+                *perms.choose(rng).unwrap(),
+            ))
+            .then(&Transformation::stack_permutation(
+                *perms.choose(rng).unwrap(),
+            ));
+        if rng.gen() {
+            ret = ret.then(&Transformation::transposition());
+        }
+        ret
+    }
+
+    #[test]
+    fn transformations_keep_answers() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        let answer = solve_unique(&puzzle);
+        for _ in 0..10 {
+            let t = random_transformation(&mut rng);
+            let transformed = t.apply(&puzzle);
+            assert_eq!(solve_unique(&transformed), t.apply(&answer));
+            assert_eq!(t.inverse().apply(&transformed), puzzle);
+            assert_eq!(t.then(&t.inverse()), Transformation::identity());
+            let u = random_transformation(&mut rng);
+            assert_eq!(t.then(&u).apply(&puzzle), u.apply(&transformed));
+        }
+    }
+
+    #[test]
+    fn single_operations() {
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        let transposed = Transformation::transposition().apply(&puzzle);
+        assert_eq!(transposed[1], puzzle[9]);
+        let swapped = Transformation::row_permutation(1, [1, 0, 2]).apply(&puzzle);
+        assert_eq!(swapped[3 * COLOR_COUNT..4 * COLOR_COUNT], puzzle[36..45]);
+        let swapped = Transformation::stack_permutation([2, 1, 0]).apply(&puzzle);
+        assert_eq!(swapped[0], puzzle[6]);
+        assert_eq!(swapped[8], puzzle[2]);
+    }
+
+    #[test]
+    fn canonical_forms() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for src in TEST_PUZZLES[..4].iter() {
+            let puzzle = parse_sudoku_array(src).unwrap();
+            let (canonical, t) = canonicalize(&puzzle);
+            assert_eq!(t.apply(&puzzle), canonical);
+            assert_eq!(canonicalize(&canonical).0, canonical);
+            for grid in [puzzle, solve_unique(&puzzle)] {
+                let other = random_transformation(&mut rng).apply(&grid);
+                assert!(is_equivalent(&grid, &other));
+                let t = find_transformation(&grid, &other).unwrap();
+                assert_eq!(t.apply(&grid), other);
+            }
+        }
+        let a = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        let b = parse_sudoku_array(TEST_PUZZLES[1]).unwrap();
+        assert!(!is_equivalent(&a, &b));
+        assert!(is_equivalent(&[0; NODE_COUNT], &[0; NODE_COUNT]));
+        assert_eq!(find_transformation(&a, &b), None);
+    }
+}
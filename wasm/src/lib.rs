@@ -3,20 +3,81 @@ use wasm_bindgen::prelude::*;
 use web_time::Instant;
 
 use core::*;
-use generator::{generate_answer, generate_puzzle_from_answer};
-use scorer::simple_score;
+use generator::{generate_answer_with_rng, generate_puzzle_from_answer};
 use solve_utils::SolveResult;
 
+mod als;
+mod bottleneck;
+mod calibration;
+mod chain;
+mod coloring;
 mod core;
+mod difficulty;
+mod exotic;
 mod fast_solver;
+mod fish;
+mod forcing;
 mod generator;
+mod isomorphism;
+mod pipeline;
+mod rating;
 mod scorer;
+mod search;
+mod single_digit;
 mod solve_utils;
 mod strategy_solver;
+mod sue_de_coq;
+mod targeted;
+mod template;
+#[cfg(test)]
+mod test_utils;
+mod uniqueness;
+mod wings;
 
 // Required by the bench lib.
-pub use generator::GeneratorConfig;
-pub use generator::generate_puzzle;
+pub use bottleneck::{analyze_bottlenecks, BottleneckAnalysis, TimelinePoint};
+pub use calibration::{
+    calibrate, load_records, parse_records, Calibration, CalibrationConfig, CalibrationError,
+    SolveRecord, WeightProfile,
+};
+pub use difficulty::{classify, generate_puzzle_with_difficulty, Classification, Difficulty};
+pub use generator::{generate_puzzle, generate_puzzle_from_mask, is_minimal, make_minimal};
+pub use generator::{GeneratorConfig, Symmetry};
+pub use isomorphism::{canonicalize, find_transformation, is_equivalent, Transformation};
+pub use pipeline::{Pipeline, PipelineBuilder, PipelinePreset, PipelineStage, RestartPolicy};
+pub use rating::{se_rating, RatedStep, SeRating, UNSOLVED_RATING};
+pub use scorer::{
+    score_breakdown, score_breakdown_with_pipeline, score_with_config, score_with_pipeline,
+    score_with_profile, simple_score, ScoreBreakdown, ScorerConfig, TechniqueScore,
+};
+pub use search::{search_puzzle_with_difficulty, search_puzzle_with_score, SearchConfig};
+
+// Strategy solvers, exposed for explaining the steps of a solve.
+pub use als::{
+    find_als, Als, AlsChainEliminator, AlsXyWingEliminator, AlsXzEliminator, DeathBlossomEliminator,
+};
+pub use chain::{Chain, ChainEliminator, ChainKind, ChainNode, ChainNodeKind};
+pub use coloring::{
+    Cluster, ConjugateGraph, MedusaEliminator, MultiColoringEliminator, SimpleColoringEliminator,
+};
+pub use core::{parse_sudoku_array, SudokuArray};
+pub use exotic::{JuniorExocetEliminator, MslsEliminator, SkLoopEliminator};
+pub use fish::{Fish, FishEliminator, FishKind};
+pub use forcing::{Fact, ForcingBranch, ForcingChain, ForcingEliminator, ForcingKind, Implication};
+pub use single_digit::{
+    EmptyRectangleEliminator, SkyscraperEliminator, TurbotFishEliminator, TwoStringKiteEliminator,
+};
+pub use strategy_solver::{
+    apply_steps, BasicEliminator, Candidate, HiddenGroupEliminator, IntersectionEliminator,
+    NodeArray, NonHiddenGroupEliminator, Step, StrategySolver, Technique, UniqueDraftValueFiller,
+};
+pub use sue_de_coq::SueDeCoqEliminator;
+pub use targeted::{generate_puzzle_with_technique, technique_uses};
+pub use template::{templates_for_color, TemplateEliminator};
+pub use uniqueness::{
+    AvoidableRectangleEliminator, BugPlusOneEliminator, UniqueRectangleEliminator,
+};
+pub use wings::{WWingEliminator, WxyzWingEliminator, XyWingEliminator, XyzWingEliminator};
 
 fn new_color_array_from_js_type(src: &[u8]) -> Result<ColorArray, JsError> {
     ColorArray::try_from(src).map_err(|err| JsError::new(&err.to_string()))
@@ -26,6 +87,39 @@ fn fill_color_array_to_js_type(src: &ColorArray, dst: &mut [u8]) {
     dst.copy_from_slice(src.as_slice());
 }
 
+fn set_js_field(obj: &js_sys::Object, key: &str, value: JsValue) {
+    js_sys::Reflect::set(obj, &key.into(), &value).unwrap();
+}
+
+fn score_breakdown_to_js_type(breakdown: &ScoreBreakdown) -> JsValue {
+    let techniques = js_sys::Array::new();
+    for t in breakdown.techniques.iter() {
+        let obj = js_sys::Object::new();
+        set_js_field(&obj, "name", t.name.into());
+        set_js_field(&obj, "weight", t.weight.into());
+        set_js_field(&obj, "eliminations", t.eliminations.into());
+        set_js_field(&obj, "score", t.score.into());
+        techniques.push(&obj);
+    }
+    let obj = js_sys::Object::new();
+    set_js_field(&obj, "techniques", techniques.into());
+    set_js_field(
+        &obj,
+        "needsBacktracking",
+        breakdown.needs_backtracking.into(),
+    );
+    set_js_field(&obj, "remainingStates", breakdown.remaining_states.into());
+    set_js_field(
+        &obj,
+        "backtrackingPenalty",
+        breakdown.backtracking_penalty.into(),
+    );
+    set_js_field(&obj, "rawScore", breakdown.raw_score.into());
+    set_js_field(&obj, "normalization", breakdown.normalization.into());
+    set_js_field(&obj, "score", breakdown.score.into());
+    obj.into()
+}
+
 #[wasm_bindgen]
 pub fn init_panic_hook() {
     console_error_panic_hook::set_once();
@@ -44,9 +138,31 @@ pub fn fast_solve(board: &mut [u8]) -> Result<usize, JsError> {
     }
 }
 
+// Mixed into the seed of an entry point for the answer, so that the answer and
+// the puzzle are not generated from the same random numbers.
+const ANSWER_SEED_MASK: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// A random answer, or the same one for the same seed.
+fn new_answer(seed: Option<u64>) -> ColorArray {
+    let config = GeneratorConfig {
+        seed: seed.map(|seed| seed ^ ANSWER_SEED_MASK),
+        ..Default::default()
+    };
+    generate_answer_with_rng(&mut config.rng())
+}
+
+fn search_config(seed: Option<u64>) -> SearchConfig {
+    let mut config = SearchConfig::default();
+    config.generator.timeout = Some(Duration::from_secs(3));
+    config.generator.seed = seed;
+    config
+}
+
+// Generates puzzles until the simple score is in the band of the difficulty,
+// from 0 (easy) to 2 (hard), for up to 3 seconds. Returns the score.
 #[wasm_bindgen]
 pub fn generate(difficulty: u8, output_puzzle: &mut [u8]) -> i32 {
-    let answer = generate_answer();
+    let answer = new_answer(None);
     let target_clues = (4 - difficulty) * 14 - 9;
     let min_score = match difficulty {
         0 => 0,
@@ -59,7 +175,7 @@ pub fn generate(difficulty: u8, output_puzzle: &mut [u8]) -> i32 {
         1 => 500,
         _ => 10000,
     };
-    let timeout= Duration::from_secs(3);
+    let timeout = Duration::from_secs(3);
     let now = Instant::now();
     loop {
         let puzzle = generate_puzzle_from_answer(
@@ -67,9 +183,10 @@ pub fn generate(difficulty: u8, output_puzzle: &mut [u8]) -> i32 {
             GeneratorConfig {
                 timeout: Some(timeout),
                 target_clues_num: target_clues as NodeIndexType,
+                ..Default::default()
             },
         );
-        let score = simple_score(&puzzle); 
+        let score = simple_score(&puzzle);
         if (score < min_score || score > max_score) && now.elapsed() < timeout {
             continue;
         }
@@ -77,3 +194,69 @@ pub fn generate(difficulty: u8, output_puzzle: &mut [u8]) -> i32 {
         return score;
     }
 }
+
+// Searches a puzzle in the tier for up to 3 seconds, see search.rs. Returns
+// the one in the closest tier if none is found. Returns the simple score.
+#[wasm_bindgen]
+pub fn generate_with_difficulty(difficulty: Difficulty, output_puzzle: &mut [u8]) -> i32 {
+    search_with_difficulty(difficulty, None, output_puzzle)
+}
+
+// Same as generate_with_difficulty, but the same seed generates the same
+// puzzle, since the search stops after a number of iterations instead of the
+// timeout.
+#[wasm_bindgen]
+pub fn generate_with_seed(difficulty: Difficulty, seed: u32, output_puzzle: &mut [u8]) -> i32 {
+    search_with_difficulty(difficulty, Some(seed as u64), output_puzzle)
+}
+
+fn search_with_difficulty(
+    difficulty: Difficulty,
+    seed: Option<u64>,
+    output_puzzle: &mut [u8],
+) -> i32 {
+    let answer = new_answer(seed);
+    let config = search_config(seed);
+    let (puzzle, _) = search_puzzle_with_difficulty(&answer, difficulty, &config);
+    fill_color_array_to_js_type(&puzzle, output_puzzle);
+    simple_score(&puzzle)
+}
+
+// Searches a puzzle whose score is between the min and max scores for up to 3
+// seconds, or returns the closest one found. A narrow band is often missed.
+// Returns the score.
+#[wasm_bindgen]
+pub fn generate_with_score(min_score: i32, max_score: i32, output_puzzle: &mut [u8]) -> i32 {
+    let answer = new_answer(None);
+    let config = search_config(None);
+    let (puzzle, score) = search_puzzle_with_score(&answer, min_score, max_score, &config);
+    fill_color_array_to_js_type(&puzzle, output_puzzle);
+    score
+}
+
+// Returns how the score of the puzzle adds up, as a plain object.
+#[wasm_bindgen]
+pub fn get_score_breakdown(board: &[u8]) -> Result<JsValue, JsError> {
+    let puzzle = new_color_array_from_js_type(board)?;
+    let breakdown = score_breakdown(&puzzle, &ScorerConfig::default());
+    Ok(score_breakdown_to_js_type(&breakdown))
+}
+
+// Generates a puzzle whose clues are exactly at the non-zero cells of the
+// mask. Returns false if the mask has less than 17 cells, or if none is found
+// before the timeout.
+#[wasm_bindgen]
+pub fn generate_from_mask(mask: &[u8], output_puzzle: &mut [u8]) -> Result<bool, JsError> {
+    let mask = new_color_array_from_js_type(mask)?.map(|c| c != 0);
+    let config = GeneratorConfig {
+        timeout: Some(Duration::from_secs(3)),
+        ..Default::default()
+    };
+    match generate_puzzle_from_mask(&mask, config) {
+        Some(puzzle) => {
+            fill_color_array_to_js_type(&puzzle, output_puzzle);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
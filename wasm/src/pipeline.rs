@@ -0,0 +1,533 @@
+use crate::*;
+use als::*;
+use chain::*;
+use coloring::*;
+use exotic::*;
+use fish::*;
+use forcing::*;
+use scorer::ScorerConfig;
+use single_digit::*;
+use solve_utils::*;
+use strategy_solver::*;
+use sue_de_coq::*;
+use template::*;
+use uniqueness::*;
+use wings::*;
+
+// A pipeline runs strategy solvers in order until they get stuck, and adds up
+// the weight of each solver times the number of states it eliminates. Each
+// stage can be disabled or re-weighted, so that different rating philosophies
+// can be modeled, and techniques can be turned off in hints.
+
+pub struct PipelineStage {
+    // Unique name of the stage, used to configure it.
+    pub name: &'static str,
+    pub weight: i32,
+    pub enabled: bool,
+    // Whether to restart from the first stage after this one, if any stage
+    // made progress since the last restart. Consecutive stages without it run
+    // as a round, like the singles and subsets of the presets.
+    pub restart: bool,
+    pub solver: Box<dyn StrategySolver>,
+}
+
+// Sets the restart flag of all the stages at once.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RestartPolicy {
+    // Restart from the first stage after any progress, so that the easiest
+    // technique is always used when it works.
+    FromEasiest,
+    // Run all the stages in order, and start over if any of them made
+    // progress. Cheaper, but harder techniques may be used where easier ones
+    // would work.
+    NextRound,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PipelinePreset {
+    // Singles, intersections, pairs and triples.
+    Basic,
+    // The techniques used by score_with_config, which are cheap enough to
+    // score many puzzles: fish, single digit patterns, wings, quads, colorings
+    // and Sue de Coq on top of the basic ones.
+    Default,
+    // Everything, including the chains, the ALS techniques, the forcing
+    // chains, the techniques which assume a unique answer, the exotic ones and
+    // templates.
+    Complete,
+}
+
+// Groups of stages, to decide which ones are enabled by a preset.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StageGroup {
+    Basic,
+    Advanced,
+    // The chains, ALS and forcing chains, which are expensive.
+    Chain,
+    Unique,
+    Exotic,
+    Template,
+}
+
+impl PipelinePreset {
+    fn enables(&self, group: StageGroup) -> bool {
+        match self {
+            PipelinePreset::Basic => group == StageGroup::Basic,
+            PipelinePreset::Default => group == StageGroup::Basic || group == StageGroup::Advanced,
+            PipelinePreset::Complete => true,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PipelineBuilder {
+    stages: Vec<PipelineStage>,
+}
+
+impl PipelineBuilder {
+    // An empty pipeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // All the known stages, with the ones of the preset enabled. The basic
+    // stages come first and run as rounds, then the others are sorted by
+    // weight and restart after any progress. The givens of the puzzle are
+    // needed by avoidable rectangles.
+    pub fn preset(preset: PipelinePreset, puzzle: &ColorArray) -> Self {
+        use StageGroup::*;
+
+        let givens = (0..NODE_COUNT)
+            .filter(|&idx| puzzle[idx] != 0)
+            .collect::<Vec<_>>();
+        let fish = |size| FishEliminator {
+            size,
+            max_fins: 2,
+            kind: FishKind::Basic,
+            budget: usize::MAX,
+        };
+        let chain = |kind, max_length| ChainEliminator { kind, max_length };
+        let aic = |als| ChainKind::Aic { grouped: true, als };
+        let forcing = |kind| ForcingEliminator {
+            kind,
+            max_depth: 16,
+            max_width: 3,
+        };
+        let naked = |group_size| NonHiddenGroupEliminator { group_size };
+        let hidden = |group_size| HiddenGroupEliminator { group_size };
+        let stages: Vec<(i32, &'static str, StageGroup, Box<dyn StrategySolver>)> = vec![
+            (1, "eliminate", Basic, Box::new(BasicEliminator {})),
+            (
+                1,
+                "naked_single",
+                Basic,
+                Box::new(UniqueDraftValueFiller {}),
+            ),
+            (2, "hidden_single", Basic, Box::new(hidden(1))),
+            (
+                3,
+                "intersection",
+                Basic,
+                Box::new(IntersectionEliminator {}),
+            ),
+            (4, "naked_pair", Basic, Box::new(naked(2))),
+            (8, "hidden_pair", Basic, Box::new(hidden(2))),
+            (9, "naked_triple", Basic, Box::new(naked(3))),
+            (18, "hidden_triple", Basic, Box::new(hidden(3))),
+            (16, "x_wing", Advanced, Box::new(fish(2))),
+            (
+                20,
+                "skyscraper",
+                Advanced,
+                Box::new(SkyscraperEliminator {}),
+            ),
+            (
+                20,
+                "two_string_kite",
+                Advanced,
+                Box::new(TwoStringKiteEliminator {}),
+            ),
+            (20, "xy_wing", Advanced, Box::new(XyWingEliminator {})),
+            (20, "naked_quad", Advanced, Box::new(naked(4))),
+            (
+                20,
+                "bug_plus_one",
+                Unique,
+                Box::new(BugPlusOneEliminator {}),
+            ),
+            (
+                22,
+                "empty_rectangle",
+                Advanced,
+                Box::new(EmptyRectangleEliminator {}),
+            ),
+            (
+                22,
+                "turbot_fish",
+                Advanced,
+                Box::new(TurbotFishEliminator {}),
+            ),
+            (24, "swordfish", Advanced, Box::new(fish(3))),
+            (24, "xyz_wing", Advanced, Box::new(XyzWingEliminator {})),
+            (24, "w_wing", Advanced, Box::new(WWingEliminator {})),
+            (
+                26,
+                "unique_rectangle",
+                Unique,
+                Box::new(UniqueRectangleEliminator {}),
+            ),
+            (
+                28,
+                "avoidable_rectangle",
+                Unique,
+                Box::new(AvoidableRectangleEliminator {
+                    givens: NodeBits::from_idx_arr(&givens),
+                }),
+            ),
+            (
+                30,
+                "simple_coloring",
+                Advanced,
+                Box::new(SimpleColoringEliminator {}),
+            ),
+            (32, "jellyfish", Advanced, Box::new(fish(4))),
+            (
+                34,
+                "multi_coloring",
+                Advanced,
+                Box::new(MultiColoringEliminator {}),
+            ),
+            (36, "wxyz_wing", Advanced, Box::new(WxyzWingEliminator {})),
+            (36, "hidden_quad", Advanced, Box::new(hidden(4))),
+            (40, "medusa", Chain, Box::new(MedusaEliminator {})),
+            (44, "x_chain", Chain, Box::new(chain(ChainKind::X, 8))),
+            (46, "xy_chain", Chain, Box::new(chain(ChainKind::Xy, 8))),
+            (48, "sue_de_coq", Advanced, Box::new(SueDeCoqEliminator {})),
+            (
+                50,
+                "als_xz",
+                Chain,
+                Box::new(AlsXzEliminator { max_size: 4 }),
+            ),
+            (52, "aic", Chain, Box::new(chain(aic(false), 10))),
+            (
+                54,
+                "als_xy_wing",
+                Chain,
+                Box::new(AlsXyWingEliminator { max_size: 4 }),
+            ),
+            (
+                58,
+                "death_blossom",
+                Chain,
+                Box::new(DeathBlossomEliminator { max_size: 4 }),
+            ),
+            (60, "als_aic", Chain, Box::new(chain(aic(true), 10))),
+            (
+                62,
+                "als_chain",
+                Chain,
+                Box::new(AlsChainEliminator {
+                    max_size: 3,
+                    max_length: 5,
+                }),
+            ),
+            (
+                63,
+                "junior_exocet",
+                Exotic,
+                Box::new(JuniorExocetEliminator {}),
+            ),
+            (64, "sk_loop", Exotic, Box::new(SkLoopEliminator {})),
+            (65, "msls", Exotic, Box::new(MslsEliminator { max_size: 4 })),
+            (66, "nishio", Chain, Box::new(forcing(ForcingKind::Nishio))),
+            (
+                70,
+                "cell_forcing",
+                Chain,
+                Box::new(forcing(ForcingKind::Cell)),
+            ),
+            (
+                72,
+                "region_forcing",
+                Chain,
+                Box::new(forcing(ForcingKind::Region)),
+            ),
+            (
+                74,
+                "template",
+                Template,
+                Box::new(TemplateEliminator {
+                    combine_pairs: true,
+                }),
+            ),
+        ];
+
+        let mut ret = Self::new();
+        for (weight, name, group, solver) in stages {
+            // Eliminating and filling singles is a round of its own, since
+            // filled nodes must be eliminated from their neighbors before the
+            // other stages run. Hidden singles to hidden triples are another.
+            let restart = !matches!(
+                name,
+                "eliminate"
+                    | "hidden_single"
+                    | "intersection"
+                    | "naked_pair"
+                    | "hidden_pair"
+                    | "naked_triple"
+            );
+            ret.stages.push(PipelineStage {
+                name,
+                weight,
+                enabled: preset.enables(group),
+                restart,
+                solver,
+            });
+        }
+        ret
+    }
+
+    // The default preset, with the techniques enabled by the config.
+    pub fn from_config(puzzle: &ColorArray, config: &ScorerConfig) -> Self {
+        let mut ret = Self::preset(PipelinePreset::Default, puzzle);
+        for name in [
+            "medusa",
+            "x_chain",
+            "xy_chain",
+            "als_xz",
+            "aic",
+            "als_xy_wing",
+            "death_blossom",
+            "als_aic",
+            "als_chain",
+            "nishio",
+            "cell_forcing",
+            "region_forcing",
+        ] {
+            ret = ret.enabled(name, config.chains);
+        }
+        for name in ["bug_plus_one", "unique_rectangle", "avoidable_rectangle"] {
+            ret = ret.enabled(name, config.assume_unique);
+        }
+        for name in ["junior_exocet", "sk_loop", "msls"] {
+            ret = ret.enabled(name, config.exotic);
+        }
+        ret
+    }
+
+    // Appends an enabled stage, which restarts after progress. Stages run in
+    // the order they are added.
+    pub fn add(mut self, name: &'static str, weight: i32, solver: Box<dyn StrategySolver>) -> Self {
+        self.stages.push(PipelineStage {
+            name,
+            weight,
+            enabled: true,
+            restart: true,
+            solver,
+        });
+        self
+    }
+
+    // Enables or disables the stage with the name, if any.
+    pub fn enabled(mut self, name: &str, enabled: bool) -> Self {
+        for stage in self.stages.iter_mut().filter(|s| s.name == name) {
+            stage.enabled = enabled;
+        }
+        self
+    }
+
+    // Whether there is a stage with the name.
+    pub fn has_stage(&self, name: &str) -> bool {
+        self.stages.iter().any(|s| s.name == name)
+    }
+
+    // Sets the weight of the stage with the name, if any. The stage is not
+    // moved, see sort_by_weight.
+    pub fn weight(mut self, name: &str, weight: i32) -> Self {
+        for stage in self.stages.iter_mut().filter(|s| s.name == name) {
+            stage.weight = weight;
+        }
+        self
+    }
+
+    // Reorders the stages by weight, keeping the order of equal weights.
+    pub fn sort_by_weight(mut self) -> Self {
+        self.stages.sort_by_key(|s| s.weight);
+        self
+    }
+
+    // Sets whether the stage with the name, if any, restarts after progress.
+    pub fn restart(mut self, name: &str, restart: bool) -> Self {
+        for stage in self.stages.iter_mut().filter(|s| s.name == name) {
+            stage.restart = restart;
+        }
+        self
+    }
+
+    pub fn restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        for stage in self.stages.iter_mut() {
+            stage.restart = restart_policy == RestartPolicy::FromEasiest;
+        }
+        self
+    }
+
+    pub fn build(self) -> Pipeline {
+        Pipeline {
+            stages: self.stages,
+        }
+    }
+}
+
+pub struct Pipeline {
+    stages: Vec<PipelineStage>,
+}
+
+impl Pipeline {
+    pub fn stages(&self) -> &[PipelineStage] {
+        &self.stages
+    }
+
+    // Runs the enabled stages until they get stuck, and returns the sum of the
+    // weighted eliminations.
+    pub fn run(&self, node_arr: &mut NodeArray) -> i32 {
+        let counts = self.run_counting(node_arr);
+        self.stages
+            .iter()
+            .zip(counts)
+            .map(|(stage, cnt)| stage.weight * cnt)
+            .sum()
+    }
+
+    // Same as run, but returns the number of eliminations of each stage, in
+    // the order of stages().
+    pub fn run_counting(&self, node_arr: &mut NodeArray) -> Vec<i32> {
+        let mut counts = vec![0; self.stages.len()];
+        let enabled = (0..self.stages.len())
+            .filter(|&i| self.stages[i].enabled)
+            .collect::<Vec<_>>();
+        'round: loop {
+            let mut progress = false;
+            for &stage_idx in enabled.iter() {
+                let stage = &self.stages[stage_idx];
+                let cnt = stage.solver.work(node_arr);
+                counts[stage_idx] += cnt;
+                if cnt > 0 && !stage.restart {
+                    // The solvers expect the board to be consistent, with the
+                    // colors of newly filled nodes eliminated from their
+                    // neighbors. This is not a technique, so it is not
+                    // counted.
+                    BasicEliminator {}.work(node_arr);
+                }
+                progress |= cnt > 0;
+                if progress && stage.restart {
+                    continue 'round;
+                }
+            }
+            if !progress {
+                return counts;
+            }
+        }
+    }
+
+    // Returns the first enabled stage which can make progress, with its steps.
+    // The steps are empty for the solvers which cannot explain their work.
+    pub fn hint(&self, node_arr: &NodeArray) -> Option<(&PipelineStage, Vec<Step>)> {
+        for stage in self.stages.iter().filter(|s| s.enabled) {
+            let steps = stage.solver.find_steps(node_arr);
+            if !steps.is_empty() {
+                return Some((stage, steps));
+            }
+            let mut board = *node_arr;
+            if stage.solver.work(&mut board) > 0 {
+                return Some((stage, steps));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn puzzle(i: usize) -> ColorArray {
+        parse_sudoku_array(TEST_PUZZLES[i]).unwrap()
+    }
+
+    #[test]
+    fn preset_enables_groups() {
+        let basic = PipelineBuilder::preset(PipelinePreset::Basic, &puzzle(0)).build();
+        let complete = PipelineBuilder::preset(PipelinePreset::Complete, &puzzle(0)).build();
+        let enabled = |p: &Pipeline, name| p.stages().iter().any(|s| s.name == name && s.enabled);
+        assert!(enabled(&basic, "hidden_triple"));
+        assert!(!enabled(&basic, "naked_quad"));
+        assert!(!enabled(&basic, "x_wing"));
+        assert!(enabled(&complete, "x_wing"));
+        assert!(enabled(&complete, "unique_rectangle"));
+        assert!(complete.stages().iter().all(|s| s.enabled));
+    }
+
+    #[test]
+    fn disabled_stage_is_not_used() {
+        let puzzle = puzzle(0);
+        let basic = PipelineBuilder::preset(PipelinePreset::Basic, &puzzle).build();
+        let mut node_arr = NodeArray::from_color_array(&puzzle);
+        basic.run(&mut node_arr);
+        assert!(basic.hint(&node_arr).is_none());
+
+        let default = PipelineBuilder::preset(PipelinePreset::Default, &puzzle).build();
+        let (stage, _) = default.hint(&node_arr).unwrap();
+        let without = PipelineBuilder::preset(PipelinePreset::Default, &puzzle)
+            .enabled(stage.name, false)
+            .build();
+        let (other, _) = without.hint(&node_arr).unwrap();
+        assert_ne!(other.name, stage.name);
+    }
+
+    #[test]
+    fn custom_pipeline() {
+        // The same singles are used, so a heavier weight gives a higher score.
+        let puzzle = puzzle(0);
+        let singles = |weight| {
+            PipelineBuilder::new()
+                .add("eliminate", 1, Box::new(BasicEliminator {}))
+                .add("naked_single", 1, Box::new(UniqueDraftValueFiller {}))
+                .add(
+                    "hidden_single",
+                    2,
+                    Box::new(HiddenGroupEliminator { group_size: 1 }),
+                )
+                .weight("hidden_single", weight)
+                .build()
+        };
+        let mut node_arr = NodeArray::from_color_array(&puzzle);
+        let light = singles(2).run(&mut node_arr);
+        let mut node_arr = NodeArray::from_color_array(&puzzle);
+        let heavy = singles(20).run(&mut node_arr);
+        assert!(heavy > light);
+    }
+
+    #[test]
+    fn restart_policies_solve_the_same() {
+        for src in TEST_PUZZLES.iter().take(6) {
+            let puzzle = parse_sudoku_array(src).unwrap();
+            let mut results = Vec::new();
+            for policy in [RestartPolicy::FromEasiest, RestartPolicy::NextRound] {
+                let mut node_arr = NodeArray::from_color_array(&puzzle);
+                let config = ScorerConfig {
+                    chains: true,
+                    ..Default::default()
+                };
+                PipelineBuilder::from_config(&puzzle, &config)
+                    .restart_policy(policy)
+                    .build()
+                    .run(&mut node_arr);
+                results.push(node_arr.to_color_array());
+            }
+            assert_eq!(results[0], results[1]);
+            assert_eq!(results[0], solve_unique(&puzzle));
+        }
+    }
+}
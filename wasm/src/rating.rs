@@ -0,0 +1,424 @@
+use std::cmp::max;
+
+use crate::*;
+use pipeline::*;
+use scorer::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// A rating compatible with the scale of Sudoku Explainer (SE). The puzzle is
+// solved by always using the easiest step available, and each step is rated by
+// its technique. The ratings are in tenths, so 23 means 2.3.
+//
+// - The rating (ER) is the rating of the hardest step of the solve.
+// - The pearl (EP) is the rating of the hardest step before the first digit
+//   is placed.
+// - The diamond (ED) is the rating of the first step.
+//
+// The ratings which cannot be found because the techniques get stuck first are
+// UNSOLVED_RATING.
+
+// SE ratings of the pipeline stages, in tenths. The singles are rated
+// separately, and the stages not listed are not used.
+const STAGE_RATINGS: [(&str, i32); 40] = [
+    ("intersection", 26),
+    ("naked_pair", 30),
+    ("x_wing", 32),
+    ("hidden_pair", 34),
+    ("naked_triple", 36),
+    ("swordfish", 38),
+    ("hidden_triple", 40),
+    ("xy_wing", 42),
+    ("xyz_wing", 44),
+    ("w_wing", 44),
+    ("unique_rectangle", 45),
+    ("avoidable_rectangle", 45),
+    ("wxyz_wing", 46),
+    ("naked_quad", 50),
+    ("sue_de_coq", 50),
+    ("jellyfish", 52),
+    ("hidden_quad", 54),
+    ("bug_plus_one", 56),
+    ("skyscraper", 66),
+    ("two_string_kite", 66),
+    ("turbot_fish", 66),
+    ("empty_rectangle", 66),
+    ("simple_coloring", 66),
+    ("x_chain", 66),
+    ("multi_coloring", 70),
+    ("xy_chain", 70),
+    ("aic", 70),
+    ("medusa", 72),
+    ("als_xz", 75),
+    ("sk_loop", 75),
+    ("nishio", 76),
+    ("als_xy_wing", 78),
+    ("als_aic", 78),
+    ("death_blossom", 80),
+    ("als_chain", 80),
+    ("msls", 80),
+    ("template", 82),
+    ("cell_forcing", 83),
+    ("region_forcing", 84),
+    ("junior_exocet", 90),
+];
+
+const LAST_DIGIT_RATING: i32 = 10;
+const HIDDEN_SINGLE_IN_SQUARE_RATING: i32 = 12;
+const HIDDEN_SINGLE_RATING: i32 = 15;
+const NAKED_SINGLE_RATING: i32 = 23;
+
+// Above the ratings of all the techniques, with their bonuses.
+pub const UNSOLVED_RATING: i32 = 120;
+
+pub(crate) fn stage_rating(name: &str) -> Option<i32> {
+    STAGE_RATINGS
+        .iter()
+        .find(|(stage, _)| *stage == name)
+        .map(|&(_, rating)| rating)
+}
+
+// The base rating of a technique, which is the name of a stage or a single.
+pub(crate) fn technique_rating(name: &str) -> Option<i32> {
+    match name {
+        "last_digit" => Some(LAST_DIGIT_RATING),
+        "hidden_single" => Some(HIDDEN_SINGLE_RATING),
+        "naked_single" => Some(NAKED_SINGLE_RATING),
+        _ => stage_rating(name),
+    }
+}
+
+// SE adds 0.1 to the rating of a chain each time its length passes 4, 6, 8,
+// 12, 16, 24, 32, ...
+fn length_bonus(length: usize) -> i32 {
+    let mut bonus = 0;
+    let mut ceil = 4;
+    let mut odd = false;
+    while length > ceil {
+        bonus += 1;
+        ceil = if odd { ceil * 4 / 3 } else { ceil * 3 / 2 };
+        odd = !odd;
+    }
+    bonus
+}
+
+fn step_rating(base: i32, step: &Step) -> i32 {
+    base + match step.technique {
+        Technique::Fish { finned: true, .. } => 1,
+        Technique::UniqueRectangle { type_num: 3 } | Technique::HiddenUniqueRectangle => 1,
+        Technique::XChain { length }
+        | Technique::XyChain { length }
+        | Technique::Aic { length }
+        | Technique::GroupedAic { length }
+        | Technique::AlsAic { length }
+        | Technique::ContinuousNiceLoop { length }
+        | Technique::DiscontinuousNiceLoop { length }
+        | Technique::AlsChain { length } => length_bonus(length),
+        // Forcing nets are the dynamic forcing chains of SE.
+        Technique::Forcing { net: true, .. } => 3,
+        _ => 0,
+    }
+}
+
+// A step of the solve with its rating. The steps of the singles and of the
+// solvers which cannot explain their work are not kept.
+#[derive(Clone, Debug)]
+pub struct RatedStep {
+    pub name: &'static str,
+    pub rating: i32,
+    pub step: Option<Step>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SeRating {
+    pub rating: i32,
+    pub pearl: i32,
+    pub diamond: i32,
+    pub hardest: Option<RatedStep>,
+    pub first: Option<RatedStep>,
+    // Whether the techniques are enough to solve the puzzle. Otherwise the
+    // ratings only cover the steps before getting stuck.
+    pub solved: bool,
+}
+
+// Returns the easiest kind of singles with their placements.
+fn find_singles(node_arr: &NodeArray) -> Option<(&'static str, i32, Vec<Candidate>)> {
+    let mut best: Option<(&'static str, i32, Vec<Candidate>)> = None;
+    let mut add = |name, rating, candidate: Candidate| match best.as_mut() {
+        Some(best) if best.1 < rating => {}
+        Some(best) if best.1 == rating => {
+            if !best.2.iter().any(|c| c.idx == candidate.idx) {
+                best.2.push(candidate);
+            }
+        }
+        _ => best = Some((name, rating, vec![candidate])),
+    };
+
+    for h in 0..HOUSE_COUNT {
+        let empty = get_all_idx_for_house(h)
+            .into_iter()
+            .filter(|&idx| node_arr[idx].color == 0)
+            .collect::<Vec<_>>();
+        for color in 1..=COLOR_COUNT as ColorType {
+            let mut nodes = empty
+                .iter()
+                .copied()
+                .filter(|&idx| node_arr[idx].available_colors.has(color));
+            if let (Some(idx), None) = (nodes.next(), nodes.next()) {
+                let (name, rating) = if empty.len() == 1 {
+                    ("last_digit", LAST_DIGIT_RATING)
+                } else if h >= 2 * COLOR_COUNT {
+                    ("hidden_single", HIDDEN_SINGLE_IN_SQUARE_RATING)
+                } else {
+                    ("hidden_single", HIDDEN_SINGLE_RATING)
+                };
+                add(name, rating, Candidate { idx, color });
+            }
+        }
+    }
+    for (idx, node) in node_arr.iter().enumerate() {
+        if node.color != 0 {
+            continue;
+        }
+        if let Some(color) = node.available_colors.get_unique() {
+            add(
+                "naked_single",
+                NAKED_SINGLE_RATING,
+                Candidate { idx, color },
+            );
+        }
+    }
+    best
+}
+
+// Returns the easiest steps of the stages, or the board after the work of a
+// stage which cannot explain it.
+fn find_easiest(
+    stages: &[(i32, &PipelineStage)],
+    node_arr: &NodeArray,
+) -> Option<(Vec<RatedStep>, Option<NodeArray>)> {
+    let mut best: Option<(Vec<RatedStep>, Option<NodeArray>)> = None;
+    let best_rating = |best: &Option<(Vec<RatedStep>, _)>| best.as_ref().map(|b| b.0[0].rating);
+    for &(base, stage) in stages {
+        if best_rating(&best).is_some_and(|rating| rating <= base) {
+            break;
+        }
+        let steps = stage.solver.find_steps(node_arr);
+        if steps.is_empty() {
+            let mut board = *node_arr;
+            if stage.solver.work(&mut board) > 0 {
+                let rated = RatedStep {
+                    name: stage.name,
+                    rating: base,
+                    step: None,
+                };
+                best = Some((vec![rated], Some(board)));
+            }
+            continue;
+        }
+        let rating = steps.iter().map(|s| step_rating(base, s)).min().unwrap();
+        if best_rating(&best).is_some_and(|r| r <= rating) {
+            continue;
+        }
+        let rated = steps
+            .into_iter()
+            .filter(|s| step_rating(base, s) == rating)
+            .map(|s| RatedStep {
+                name: stage.name,
+                rating,
+                step: Some(s),
+            })
+            .collect();
+        best = Some((rated, None));
+    }
+    best
+}
+
+// The steps of a solve which always uses the easiest steps available.
+pub(crate) struct RatedSolve {
+    pub steps: Vec<RatedStep>,
+    // Index of the first step which places a digit.
+    pub first_placement: Option<usize>,
+    pub points: Vec<SolvePoint>,
+    pub solved: bool,
+}
+
+// A point of the solve, where the easiest steps available are made.
+pub(crate) struct SolvePoint {
+    // Index of the first step made at the point.
+    pub first_step: usize,
+    // Number of candidates placed or eliminated by the easiest steps.
+    pub options: usize,
+    // Candidates left before the steps.
+    pub remaining: i32,
+}
+
+pub(crate) fn rated_solve(puzzle: &ColorArray, config: &ScorerConfig) -> RatedSolve {
+    rated_solve_up_to(puzzle, config, i32::MAX, None)
+}
+
+// Same as rated_solve, but only the stages rated up to max_rating are used,
+// except the one named without, if any.
+pub(crate) fn rated_solve_up_to(
+    puzzle: &ColorArray,
+    config: &ScorerConfig,
+    max_rating: i32,
+    without: Option<&str>,
+) -> RatedSolve {
+    // SE rates the hard puzzles by their chains, so they are always used.
+    let config = ScorerConfig {
+        chains: true,
+        ..*config
+    };
+    let pipeline = PipelineBuilder::from_config(puzzle, &config).build();
+    let mut stages = pipeline
+        .stages()
+        .iter()
+        .filter(|s| s.enabled && Some(s.name) != without)
+        .filter_map(|s| stage_rating(s.name).map(|rating| (rating, s)))
+        .filter(|&(rating, _)| rating <= max_rating)
+        .collect::<Vec<_>>();
+    stages.sort_by_key(|&(rating, _)| rating);
+
+    let mut ret = RatedSolve {
+        steps: Vec::new(),
+        first_placement: None,
+        points: Vec::new(),
+        solved: false,
+    };
+    let mut node_arr = NodeArray::from_color_array(puzzle);
+    loop {
+        BasicEliminator {}.work(&mut node_arr);
+        if node_arr.iter().all(|node| node.color != 0) {
+            ret.solved = node_arr.validate_colors(true);
+            return ret;
+        }
+
+        let remaining = count_remaining_state(&node_arr);
+        let first_step = ret.steps.len();
+        let options = if let Some((name, rating, placements)) = find_singles(&node_arr) {
+            let options = placements.len();
+            for c in placements {
+                node_arr[c.idx].color = c.color;
+                node_arr[c.idx].available_colors.clear();
+            }
+            ret.first_placement.get_or_insert(ret.steps.len());
+            ret.steps.push(RatedStep {
+                name,
+                rating,
+                step: None,
+            });
+            options
+        } else if let Some((rated, board)) = find_easiest(&stages, &node_arr) {
+            let options = match board {
+                Some(board) => {
+                    node_arr = board;
+                    (remaining - count_remaining_state(&node_arr)) as usize
+                }
+                None => {
+                    let steps = rated
+                        .iter()
+                        .filter_map(|r| r.step.clone())
+                        .collect::<Vec<_>>();
+                    apply_steps(&mut node_arr, &steps) as usize
+                }
+            };
+            ret.steps.extend(rated);
+            options
+        } else {
+            return ret;
+        };
+        ret.points.push(SolvePoint {
+            first_step,
+            options,
+            remaining,
+        });
+    }
+}
+
+pub fn se_rating(puzzle: &ColorArray, config: &ScorerConfig) -> SeRating {
+    let solve = rated_solve(puzzle, config);
+    let max_rating = |steps: &[RatedStep]| steps.iter().map(|s| s.rating).max().unwrap_or(0);
+    let stuck_rating = if solve.solved { 0 } else { UNSOLVED_RATING };
+    let pearl = match solve.first_placement {
+        Some(i) => max_rating(&solve.steps[..=i]),
+        None => max(max_rating(&solve.steps), stuck_rating),
+    };
+    // The first hardest step.
+    let hardest = solve.steps.iter().rev().max_by_key(|s| s.rating).cloned();
+    SeRating {
+        rating: max(max_rating(&solve.steps), stuck_rating),
+        pearl,
+        diamond: solve.steps.first().map_or(stuck_rating, |s| s.rating),
+        hardest,
+        first: solve.steps.first().cloned(),
+        solved: solve.solved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn length_bonus() {
+        let bonus = [4, 5, 6, 7, 8, 9, 12, 13, 16, 17].map(super::length_bonus);
+        assert_eq!(bonus, [0, 1, 1, 2, 2, 3, 3, 4, 4, 5]);
+    }
+
+    #[test]
+    fn singles_only() {
+        // Each empty node is the last one of its row.
+        let mut puzzle = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        for idx in [0, 10, 20] {
+            puzzle[idx] = 0;
+        }
+        let rating = se_rating(&puzzle, &ScorerConfig::default());
+        assert!(rating.solved);
+        assert_eq!(rating.rating, 10);
+        assert_eq!(rating.pearl, 10);
+        assert_eq!(rating.diamond, 10);
+        assert_eq!(rating.hardest.unwrap().name, "last_digit");
+    }
+
+    #[test]
+    fn ratings_are_ordered() {
+        for src in TEST_PUZZLES.iter() {
+            let puzzle = parse_sudoku_array(src).unwrap();
+            let rating = se_rating(&puzzle, &ScorerConfig::default());
+            assert!(rating.diamond <= rating.pearl);
+            assert!(rating.pearl <= rating.rating);
+            if !rating.solved {
+                assert_eq!(rating.rating, UNSOLVED_RATING);
+                continue;
+            }
+            assert_eq!(rating.hardest.unwrap().rating, rating.rating);
+            assert_eq!(rating.first.unwrap().rating, rating.diamond);
+        }
+    }
+
+    #[test]
+    fn unsolved_is_hardest() {
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[7]).unwrap();
+        let rating = se_rating(&puzzle, &ScorerConfig::default());
+        assert!(!rating.solved);
+        assert_eq!(rating.rating, UNSOLVED_RATING);
+        assert_eq!(rating.pearl, UNSOLVED_RATING);
+        assert!(STAGE_RATINGS.iter().all(|&(_, r)| r + 10 < UNSOLVED_RATING));
+    }
+
+    #[test]
+    fn chain_bonus_by_length() {
+        let step = |technique| Step {
+            technique,
+            nodes: vec![0, 1, 2, 3],
+            eliminations: Vec::new(),
+        };
+        // Four cells, but eight candidates.
+        let rating = step_rating(70, &step(Technique::XyChain { length: 8 }));
+        assert_eq!(rating, 72);
+        let rating = step_rating(66, &step(Technique::XChain { length: 4 }));
+        assert_eq!(rating, 66);
+    }
+}
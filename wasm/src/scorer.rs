@@ -1,9 +1,10 @@
 use crate::*;
-
+use calibration::{CalibrationError, WeightProfile};
+use pipeline::*;
 use solve_utils::*;
 use strategy_solver::*;
 
-fn count_remaining_state(node_arr: &NodeArray) -> i32 {
+pub(crate) fn count_remaining_state(node_arr: &NodeArray) -> i32 {
     let mut cnt = 0;
     for &c in node_arr {
         if c.color != 0 {
@@ -14,6 +15,23 @@ fn count_remaining_state(node_arr: &NodeArray) -> i32 {
     cnt
 }
 
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ScorerConfig {
+    // Whether to use the techniques which assume the puzzle has a unique
+    // answer. They may eliminate the answers of puzzles with multiple answers.
+    pub assume_unique: bool,
+    // Whether to use the exotic pattern techniques, which are expensive and
+    // only needed by the hardest puzzles.
+    pub exotic: bool,
+    // Whether to use the chains, the ALS techniques and the forcing chains,
+    // which are expensive. Without them, the puzzles which need them are
+    // scored by backtracking.
+    pub chains: bool,
+}
+
+// The scorer of simple_score, which is cheap enough for the generator to
+// score every puzzle it visits. It only uses the singles and subsets, and
+// stops as soon as eliminating and filling singles gets stuck.
 fn non_backtracing_scorer(node_arr: &mut NodeArray) -> i32 {
     let mut score = 0;
 
@@ -30,8 +48,8 @@ fn non_backtracing_scorer(node_arr: &mut NodeArray) -> i32 {
         let start_score = *score;
         loop {
             let last_score = *score;
-            *score += 1 * basic_eliminator.work(node_arr);
-            *score += 1 * basic_filler.work(node_arr);
+            *score += basic_eliminator.work(node_arr);
+            *score += basic_filler.work(node_arr);
             if last_score == *score {
                 break;
             }
@@ -51,18 +69,205 @@ fn non_backtracing_scorer(node_arr: &mut NodeArray) -> i32 {
         score += 18 * hidden_group_eliminator_3.work(node_arr);
     }
 
-    return score;
+    score
 }
 
 // Max score: 10000.
+//
+// The score of the first version, kept as it was since the difficulty bands
+// of lib.rs::generate and the generator depend on it. The techniques added
+// since are scored by score_with_config, which runs the pipeline instead.
 pub fn simple_score(puzzle: &ColorArray) -> i32 {
     let mut node_arr = NodeArray::from_color_array(puzzle);
     let score = non_backtracing_scorer(&mut node_arr);
     let total_score = if !node_arr.validate_colors(true) {
         // If need backtracing.
-        score + 100 * count_remaining_state(&node_arr)
+        score + BACKTRACKING_WEIGHT * count_remaining_state(&node_arr)
     } else {
         score
     };
-    total_score * 100 / (9 * 9 * 9)
+    total_score * NORMALIZATION_NUM / NORMALIZATION_DEN
+}
+
+pub fn score_with_config(puzzle: &ColorArray, config: &ScorerConfig) -> i32 {
+    score_with_pipeline(
+        puzzle,
+        &PipelineBuilder::from_config(puzzle, config).build(),
+    )
+}
+
+// Scores with the stage weights of a calibrated profile.
+pub fn score_with_profile(
+    puzzle: &ColorArray,
+    config: &ScorerConfig,
+    profile: &WeightProfile,
+) -> Result<i32, CalibrationError> {
+    let builder = profile.apply(PipelineBuilder::from_config(puzzle, config))?;
+    Ok(score_with_pipeline(puzzle, &builder.build()))
+}
+
+pub fn score_with_pipeline(puzzle: &ColorArray, pipeline: &Pipeline) -> i32 {
+    score_breakdown_with_pipeline(puzzle, pipeline).score
+}
+
+// Each remaining candidate costs this much if backtracking is needed.
+const BACKTRACKING_WEIGHT: i32 = 100;
+// The raw score is multiplied by NORMALIZATION_NUM / NORMALIZATION_DEN.
+const NORMALIZATION_NUM: i32 = 100;
+const NORMALIZATION_DEN: i32 = (NODE_COUNT * COLOR_COUNT) as i32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TechniqueScore {
+    pub name: &'static str,
+    pub weight: i32,
+    pub eliminations: i32,
+    // Weight times eliminations.
+    pub score: i32,
+}
+
+// How the score of a puzzle adds up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreBreakdown {
+    // The enabled stages of the pipeline, in order.
+    pub techniques: Vec<TechniqueScore>,
+    // Whether the techniques got stuck, so trial and error is needed.
+    pub needs_backtracking: bool,
+    // Candidates left when the techniques got stuck.
+    pub remaining_states: i32,
+    pub backtracking_penalty: i32,
+    // Sum of the technique scores and the backtracking penalty.
+    pub raw_score: i32,
+    pub normalization: f64,
+    pub score: i32,
+}
+
+pub fn score_breakdown(puzzle: &ColorArray, config: &ScorerConfig) -> ScoreBreakdown {
+    score_breakdown_with_pipeline(
+        puzzle,
+        &PipelineBuilder::from_config(puzzle, config).build(),
+    )
+}
+
+pub fn score_breakdown_with_pipeline(puzzle: &ColorArray, pipeline: &Pipeline) -> ScoreBreakdown {
+    let mut node_arr = NodeArray::from_color_array(puzzle);
+    let counts = pipeline.run_counting(&mut node_arr);
+    let techniques = pipeline
+        .stages()
+        .iter()
+        .zip(counts)
+        .filter(|(stage, _)| stage.enabled)
+        .map(|(stage, eliminations)| TechniqueScore {
+            name: stage.name,
+            weight: stage.weight,
+            eliminations,
+            score: stage.weight * eliminations,
+        })
+        .collect::<Vec<_>>();
+
+    let needs_backtracking = !node_arr.validate_colors(true);
+    let remaining_states = if needs_backtracking {
+        count_remaining_state(&node_arr)
+    } else {
+        0
+    };
+    let backtracking_penalty = BACKTRACKING_WEIGHT * remaining_states;
+    let raw_score = techniques.iter().map(|t| t.score).sum::<i32>() + backtracking_penalty;
+    ScoreBreakdown {
+        techniques,
+        needs_backtracking,
+        remaining_states,
+        backtracking_penalty,
+        raw_score,
+        normalization: NORMALIZATION_NUM as f64 / NORMALIZATION_DEN as f64,
+        score: raw_score * NORMALIZATION_NUM / NORMALIZATION_DEN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    // The scores of the test puzzles by the first version of simple_score.
+    const TEST_PUZZLE_SCORES: [i32; 11] = [
+        908, 1494, 2665, 2427, 1729, 1341, 2898, 3534, 3019, 3330, 248,
+    ];
+
+    #[test]
+    fn simple_score_of_test_puzzles() {
+        for (src, &score) in TEST_PUZZLES.iter().zip(TEST_PUZZLE_SCORES.iter()) {
+            assert_eq!(
+                simple_score(&parse_sudoku_array(src).unwrap()),
+                score,
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn breakdown_adds_up() {
+        // The raw scores and remaining states of the test puzzles.
+        let expected = [
+            (1163, 0),
+            (1227, 0),
+            (1598, 0),
+            (1157, 0),
+            (11891, 110),
+            (1115, 0),
+            (20963, 202),
+            (25766, 254),
+            (21711, 212),
+            (24282, 239),
+            (1809, 0),
+        ];
+        for (i, src) in TEST_PUZZLES.iter().enumerate() {
+            let puzzle = parse_sudoku_array(src).unwrap();
+            let breakdown = score_breakdown(&puzzle, &ScorerConfig::default());
+            let (raw_score, remaining_states) = expected[i];
+            assert_eq!(
+                breakdown.score,
+                score_with_config(&puzzle, &ScorerConfig::default())
+            );
+            assert_eq!(breakdown.raw_score, raw_score, "{}", src);
+            assert_eq!(breakdown.remaining_states, remaining_states, "{}", src);
+            assert_eq!(breakdown.needs_backtracking, remaining_states > 0);
+            assert_eq!(breakdown.backtracking_penalty, 100 * remaining_states);
+            let sum = breakdown.techniques.iter().map(|t| t.score).sum::<i32>();
+            assert_eq!(sum + breakdown.backtracking_penalty, raw_score);
+        }
+    }
+
+    #[test]
+    fn score_with_loaded_profile() {
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        let config = ScorerConfig::default();
+        let empty = WeightProfile::default();
+        let score = score_with_config(&puzzle, &config);
+        assert_eq!(score_with_profile(&puzzle, &config, &empty).unwrap(), score);
+        let doubled = WeightProfile::parse("eliminate=2\nnaked_single=2\n").unwrap();
+        assert!(score_with_profile(&puzzle, &config, &doubled).unwrap() > score);
+        let unknown = WeightProfile {
+            weights: vec![("x_wings".into(), 12)],
+        };
+        assert!(matches!(
+            score_with_profile(&puzzle, &config, &unknown),
+            Err(CalibrationError::UnknownStage(_))
+        ));
+    }
+
+    #[test]
+    fn breakdown_of_singles() {
+        let mut puzzle = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        for idx in [0, 10, 20] {
+            puzzle[idx] = 0;
+        }
+        let breakdown = score_breakdown(&puzzle, &ScorerConfig::default());
+        assert!(!breakdown.needs_backtracking);
+        assert_eq!(breakdown.backtracking_penalty, 0);
+        for t in breakdown.techniques.iter() {
+            let is_single = t.name == "eliminate" || t.name == "naked_single";
+            assert!(is_single || t.eliminations == 0, "{}", t.name);
+        }
+    }
 }
@@ -0,0 +1,263 @@
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+};
+
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+
+use crate::*;
+use difficulty::*;
+use fast_solver::*;
+use generator::*;
+use scorer::*;
+
+// Generation of puzzles toward a target by simulated annealing, instead of
+// generating puzzles until one hits the target. The search starts from a
+// generated puzzle and moves to a neighbor by adding, removing or swapping the
+// clues of an orbit of the symmetry, keeping the answer unique. The cost of a
+// puzzle is its distance to the target, and the neighbors are scored only
+// once, since the search often comes back to the same puzzles. Each neighbor
+// is scored by a full solve, not incrementally, so an iteration takes up to
+// about 4 milliseconds natively, for the Extreme tier.
+
+#[derive(Clone, Copy)]
+pub struct SearchConfig {
+    // The timeout, seed and symmetry are used by the search. The start puzzle
+    // has the target number of clues, but at least 27 to be found quickly.
+    // With a seed, the timeout is turned into a number of iterations, see
+    // TryBudget, so the same seed always finds the same puzzle.
+    pub generator: GeneratorConfig,
+    pub scorer: ScorerConfig,
+    // The default fits in the 3 seconds given by generate_with_difficulty in
+    // the browser, see SEARCH_ITERATIONS_PER_SEC.
+    pub max_iterations: usize,
+    // The initial temperature, in the unit of the cost. It cools down
+    // linearly to 0 by the max iterations.
+    pub temperature: f64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            generator: GeneratorConfig {
+                target_clues_num: 27,
+                ..Default::default()
+            },
+            scorer: ScorerConfig::default(),
+            max_iterations: 400,
+            temperature: 20.0,
+        }
+    }
+}
+
+// Returns the neighbor of the puzzle made by a random move with a unique
+// answer. The removals which break the uniqueness are retried with the other
+// clues, otherwise the search would drift to adding clues.
+fn neighbor(
+    puzzle: &ColorArray,
+    answer: &ColorArray,
+    symmetry: Symmetry,
+    rng: &mut impl Rng,
+) -> Option<ColorArray> {
+    let mut clues = (0..NODE_COUNT)
+        .filter(|&idx| puzzle[idx] != 0)
+        .collect::<Vec<_>>();
+    let empty = (0..NODE_COUNT)
+        .filter(|&idx| puzzle[idx] == 0)
+        .choose(rng)?;
+    let mut ret = *puzzle;
+    match rng.gen_range(0..3) {
+        // Adding clues keeps the answer unique.
+        0 => {
+            for idx in symmetry.orbit(empty) {
+                ret[idx] = answer[idx];
+            }
+            return Some(ret);
+        }
+        1 => {}
+        // Swap.
+        _ => {
+            for idx in symmetry.orbit(empty) {
+                ret[idx] = answer[idx];
+            }
+            clues.retain(|&idx| !symmetry.orbit(empty).contains(&idx));
+        }
+    }
+    clues.shuffle(rng);
+    for clue in clues {
+        let orbit = symmetry.orbit(clue);
+        for &idx in orbit.iter() {
+            ret[idx] = 0;
+        }
+        if check_puzzle_has_unique_answer(&ret, answer) {
+            return Some(ret);
+        }
+        for &idx in orbit.iter() {
+            ret[idx] = answer[idx];
+        }
+    }
+    None
+}
+
+// Iterations of the search made in a second, about, in the browser. Measured
+// natively in release by searching the puzzles of 10 seeded answers: 265 for
+// the Extreme tier, which is the slowest, 530 for Expert, 1000 to 1300 for the
+// easier tiers and 800 to 1400 for score bands. WebAssembly is taken as about
+// twice as slow.
+const SEARCH_ITERATIONS_PER_SEC: f64 = 130.0;
+
+// Searches the puzzle of the answer with the least cost, stopping once the
+// cost is 0. Returns the best puzzle found with its cost.
+fn search_puzzle(
+    answer: &ColorArray,
+    config: &SearchConfig,
+    mut cost: impl FnMut(&ColorArray) -> i32,
+) -> (ColorArray, i32) {
+    let mut budget = TryBudget::new(&config.generator, SEARCH_ITERATIONS_PER_SEC);
+    let max_iterations = budget.max_tries().map_or(config.max_iterations, |tries| {
+        min(tries, config.max_iterations)
+    });
+    let mut rng = config.generator.rng();
+    let start = generate_puzzle_from_answer(
+        answer,
+        GeneratorConfig {
+            timeout: None,
+            target_clues_num: max(config.generator.target_clues_num, 27),
+            ..config.generator
+        },
+    );
+    let mut costs = HashMap::new();
+    let mut current = (start, cost(&start));
+    costs.insert(current.0, current.1);
+    let mut best = current;
+
+    for i in 0..max_iterations {
+        if best.1 == 0 || budget.is_exhausted() {
+            break;
+        }
+        budget.add_try();
+        let Some(next) = neighbor(&current.0, answer, config.generator.symmetry, &mut rng) else {
+            continue;
+        };
+        let next_cost = *costs.entry(next).or_insert_with(|| cost(&next));
+        let delta = (next_cost - current.1) as f64;
+        let temperature = config.temperature * (1.0 - i as f64 / max_iterations as f64);
+        if delta <= 0.0 || rng.gen::<f64>() < (-delta / temperature).exp() {
+            current = (next, next_cost);
+            if current.1 < best.1 {
+                best = current;
+            }
+        }
+    }
+    best
+}
+
+// Searches a puzzle of the answer whose score by the scorer config is in the
+// band. Returns the closest one with its score if none is found before the
+// max iterations or the timeout.
+pub fn search_puzzle_with_score(
+    answer: &ColorArray,
+    min_score: i32,
+    max_score: i32,
+    config: &SearchConfig,
+) -> (ColorArray, i32) {
+    let scorer = config.scorer;
+    let distance = |score: i32| max(min_score - score, max(score - max_score, 0));
+    let (puzzle, _) = search_puzzle(answer, config, |puzzle| {
+        distance(score_with_config(puzzle, &scorer))
+    });
+    (puzzle, score_with_config(&puzzle, &scorer))
+}
+
+// Each tier between the tier of the puzzle and the target costs this much, on
+// top of the distance of the rating to the ratings of the tier.
+const TIER_COST: i32 = 100;
+
+// Searches a puzzle of the answer in the tier. Returns the one in the closest
+// tier if none is found before the max iterations or the timeout. The scorer
+// config is not used, like by classify.
+pub fn search_puzzle_with_difficulty(
+    answer: &ColorArray,
+    difficulty: Difficulty,
+    config: &SearchConfig,
+) -> (ColorArray, Classification) {
+    let (min_rating, max_rating) = difficulty.rating_bounds();
+    let (puzzle, _) = search_puzzle(answer, config, |puzzle| {
+        let classification = classify(puzzle);
+        let tiers = (classification.difficulty as i32 - difficulty as i32).abs();
+        let rating = classification.rating;
+        let distance = max(min_rating - rating, max(rating - max_rating, 0));
+        tiers * TIER_COST + distance
+    });
+    (puzzle, classify(&puzzle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use test_utils::*;
+
+    fn config(seed: u64) -> SearchConfig {
+        SearchConfig {
+            generator: GeneratorConfig {
+                target_clues_num: 27,
+                seed: Some(seed),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn search_score_band() {
+        let answer = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        let (puzzle, score) = search_puzzle_with_score(&answer, 260, 280, &config(1));
+        assert_eq!(solve(&puzzle), SolveResult::Unique(answer));
+        assert_eq!(score, score_with_config(&puzzle, &ScorerConfig::default()));
+        assert!((260..=280).contains(&score), "{}", score);
+    }
+
+    #[test]
+    fn search_difficulty() {
+        let answer = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        let (puzzle, classification) =
+            search_puzzle_with_difficulty(&answer, Difficulty::Medium, &config(2));
+        assert_eq!(solve(&puzzle), SolveResult::Unique(answer));
+        assert_eq!(classification.difficulty, Difficulty::Medium);
+    }
+
+    #[test]
+    fn same_seed_same_puzzle_by_timeout() {
+        // The band is not reached in the iterations of the timeout.
+        let answer = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        let mut config = config(4);
+        config.generator.timeout = Some(Duration::from_millis(100));
+        let (puzzle, score) = search_puzzle_with_score(&answer, 5000, 6000, &config);
+        assert!(score < 5000);
+        assert_eq!(
+            search_puzzle_with_score(&answer, 5000, 6000, &config),
+            (puzzle, score)
+        );
+    }
+
+    #[test]
+    fn search_keeps_symmetry() {
+        let answer = solve_unique(&parse_sudoku_array(TEST_PUZZLES[0]).unwrap());
+        let symmetry = Symmetry::Rotational180;
+        let mut config = config(3);
+        config.generator.symmetry = symmetry;
+        let (puzzle, _) = search_puzzle_with_score(&answer, 600, 700, &config);
+        assert_eq!(solve(&puzzle), SolveResult::Unique(answer));
+        for idx in 0..NODE_COUNT {
+            let clue = puzzle[idx] != 0;
+            assert!(symmetry
+                .orbit(idx)
+                .iter()
+                .all(|&j| (puzzle[j] != 0) == clue));
+        }
+    }
+}
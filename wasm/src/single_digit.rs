@@ -0,0 +1,300 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Patterns working on the candidates of a single color.
+//
+// A strong link is a house holding the color in only two cells, so one of
+// them must be the color. A turbot fish is a chain of a strong link A-B, a
+// weak link B-C (i.e. B sees C) and a strong link C-D. Either A or D must be
+// the color, so it can be eliminated from the cells seeing both A and D.
+// Skyscrapers and two-string kites are the turbot fishes whose strong links
+// are on rows and columns only.
+
+#[derive(Clone, Copy)]
+struct StrongLink {
+    house: usize,
+    nodes: [NodeIndexType; 2],
+}
+
+fn find_strong_links(candidates: &NodeBits) -> Vec<StrongLink> {
+    let mut ret: Vec<StrongLink> = Vec::new();
+    for (house, house_bits) in HOUSE_BITS_ARR.iter().enumerate() {
+        let nodes = house_bits.intersect(candidates);
+        if nodes.count() != 2 {
+            continue;
+        }
+        let mut iter = nodes.iter();
+        let nodes = [iter.next().unwrap(), iter.next().unwrap()];
+        // Two nodes in the same row and square are linked by both houses.
+        if ret.iter().all(|l| l.nodes != nodes) {
+            ret.push(StrongLink { house, nodes });
+        }
+    }
+    ret
+}
+
+fn house_kind(house: usize) -> usize {
+    house / COLOR_COUNT
+}
+
+const ROW: usize = 0;
+const COL: usize = 1;
+
+fn classify_turbot_fish(
+    link_1: &StrongLink,
+    link_2: &StrongLink,
+    b: NodeIndexType,
+    c: NodeIndexType,
+) -> Technique {
+    let kind_1 = house_kind(link_1.house);
+    let kind_2 = house_kind(link_2.house);
+    if kind_1 == kind_2 && kind_1 == ROW && col_idx(b) == col_idx(c)
+        || kind_1 == kind_2 && kind_1 == COL && row_idx(b) == row_idx(c)
+    {
+        Technique::Skyscraper
+    } else if kind_1 != kind_2 && kind_1 <= COL && kind_2 <= COL && sqr_idx(b) == sqr_idx(c) {
+        Technique::TwoStringKite
+    } else {
+        Technique::TurbotFish
+    }
+}
+
+fn find_turbot_fishes(node_arr: &NodeArray, technique: Technique) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for color in 1..=COLOR_COUNT as ColorType {
+        let candidates = get_nodes_with_available_color(node_arr, color);
+        let links = find_strong_links(&candidates);
+        for (i, link_1) in links.iter().enumerate() {
+            for link_2 in links[i + 1..].iter() {
+                for [a, b] in [link_1.nodes, [link_1.nodes[1], link_1.nodes[0]]] {
+                    for [c, d] in [link_2.nodes, [link_2.nodes[1], link_2.nodes[0]]] {
+                        let nodes = [a, b, c, d];
+                        if a == c || a == d || b == c || b == d {
+                            continue;
+                        }
+                        if !NEIGHBOR_BITS_MAP[b].has(c)
+                            || classify_turbot_fish(link_1, link_2, b, c) != technique
+                        {
+                            continue;
+                        }
+                        let eliminations = NEIGHBOR_BITS_MAP[a]
+                            .intersect(&NEIGHBOR_BITS_MAP[d])
+                            .intersect(&candidates)
+                            .minus(&NodeBits::from_idx_arr(&nodes));
+                        if eliminations.is_empty() {
+                            continue;
+                        }
+                        steps.push(Step {
+                            technique,
+                            nodes: nodes.to_vec(),
+                            eliminations: eliminations
+                                .iter()
+                                .map(|idx| Candidate { idx, color })
+                                .collect(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    steps
+}
+
+// Two strong links on parallel lines, whose ends B and C are on the same
+// perpendicular line.
+pub struct SkyscraperEliminator {}
+
+impl StrategySolver for SkyscraperEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        find_turbot_fishes(node_arr, Technique::Skyscraper)
+    }
+}
+
+// A strong link on a row and a strong link on a column, whose ends B and C
+// are in the same square.
+pub struct TwoStringKiteEliminator {}
+
+impl StrategySolver for TwoStringKiteEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        find_turbot_fishes(node_arr, Technique::TwoStringKite)
+    }
+}
+
+// The turbot fishes which are neither skyscrapers nor two-string kites, e.g.
+// with a strong link in a square.
+pub struct TurbotFishEliminator {}
+
+impl StrategySolver for TurbotFishEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        find_turbot_fishes(node_arr, Technique::TurbotFish)
+    }
+}
+
+// An empty rectangle is a square whose candidates are all on one row r and one
+// column c of the square, but not on only one of them. With a strong link on
+// a row, one end P on column c and the other end Q outside the square's
+// columns, the cell T on row r and Q's column cannot be the color: otherwise
+// the square's color has to be on column c, so P is not the color and Q is,
+// which sees T. Same for a strong link on a column.
+pub struct EmptyRectangleEliminator {}
+
+impl StrategySolver for EmptyRectangleEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for color in 1..=COLOR_COUNT as ColorType {
+            let candidates = get_nodes_with_available_color(node_arr, color);
+            let links = find_strong_links(&candidates);
+            for sqr in 0..COLOR_COUNT {
+                let sqr_candidates = candidates.intersect(&HOUSE_BITS_ARR[2 * COLOR_COUNT + sqr]);
+                for (r, c) in empty_rectangle_lines(&sqr_candidates, sqr) {
+                    for link in links.iter() {
+                        let kind = house_kind(link.house);
+                        if kind > COL {
+                            continue;
+                        }
+                        for [p, q] in [link.nodes, [link.nodes[1], link.nodes[0]]] {
+                            // The target is the intersection of the line of the
+                            // empty rectangle and the line through Q.
+                            let target = if kind == ROW {
+                                if col_idx(p) != c || col_idx(q) / 3 == sqr % 3 {
+                                    continue;
+                                }
+                                r * COLOR_COUNT + col_idx(q)
+                            } else {
+                                if row_idx(p) != r || row_idx(q) / 3 == sqr / 3 {
+                                    continue;
+                                }
+                                row_idx(q) * COLOR_COUNT + c
+                            };
+                            if sqr_idx(p) == sqr || !candidates.has(target) {
+                                continue;
+                            }
+                            let nodes = sqr_candidates.union(&NodeBits::from_idx_arr(&link.nodes));
+                            steps.push(Step {
+                                technique: Technique::EmptyRectangle,
+                                nodes: nodes.iter().collect(),
+                                eliminations: vec![Candidate { idx: target, color }],
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        steps
+    }
+}
+
+// Returns the (row, column) pairs of the empty rectangles in the square. There
+// can be two of them, e.g. when the candidates are on a diagonal of two nodes.
+fn empty_rectangle_lines(sqr_candidates: &NodeBits, sqr: usize) -> Vec<(usize, usize)> {
+    let mut ret = Vec::new();
+    for idx in get_all_idx_for_sqr(sqr) {
+        let (r, c) = (row_idx(idx), col_idx(idx));
+        let row_bits = HOUSE_BITS_ARR[r];
+        let col_bits = HOUSE_BITS_ARR[COLOR_COUNT + c];
+        if !sqr_candidates.minus(&row_bits.union(&col_bits)).is_empty() {
+            continue;
+        }
+        if sqr_candidates.minus(&row_bits).is_empty() || sqr_candidates.minus(&col_bits).is_empty()
+        {
+            continue;
+        }
+        ret.push((r, c));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    // Keeps color 1 only at the given nodes of the house.
+    fn keep_in_house(node_arr: &mut NodeArray, house: usize, nodes: &[NodeIndexType]) {
+        for idx in get_all_idx_for_house(house) {
+            if !nodes.contains(&idx) {
+                node_arr[idx].available_colors.del(1);
+            }
+        }
+    }
+
+    fn eliminated_nodes(steps: &[Step]) -> Vec<NodeIndexType> {
+        let mut ret = steps
+            .iter()
+            .flat_map(|s| s.eliminations.iter().map(|e| e.idx))
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    #[test]
+    fn skyscraper() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // Column 1 holds 1 at r1 and r8, column 4 holds 1 at r2 and r8.
+        keep_in_house(&mut node_arr, COLOR_COUNT + 1, &[10, 73]);
+        keep_in_house(&mut node_arr, COLOR_COUNT + 4, &[22, 76]);
+
+        let steps = SkyscraperEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![12, 14, 18, 20]);
+        assert!(TwoStringKiteEliminator {}.find_steps(&node_arr).is_empty());
+    }
+
+    #[test]
+    fn two_string_kite() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // Row 0 holds 1 at c1 and c7, column 0 holds 1 at r2 and r6.
+        keep_in_house(&mut node_arr, 0, &[1, 7]);
+        keep_in_house(&mut node_arr, COLOR_COUNT, &[18, 54]);
+
+        let steps = TwoStringKiteEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![61]);
+        assert!(SkyscraperEliminator {}.find_steps(&node_arr).is_empty());
+    }
+
+    #[test]
+    fn turbot_fish() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // Row 0 holds 1 at c2 and c6, square 8 holds 1 at r6c6 and r8c8.
+        keep_in_house(&mut node_arr, 0, &[2, 6]);
+        keep_in_house(&mut node_arr, 2 * COLOR_COUNT + 8, &[60, 80]);
+
+        let steps = TurbotFishEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![74]);
+    }
+
+    #[test]
+    fn empty_rectangle() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // Square 0 holds 1 only on row 0 and column 0, row 5 holds 1 at c0 and
+        // c6.
+        keep_in_house(&mut node_arr, 2 * COLOR_COUNT, &[0, 1, 2, 9, 18]);
+        keep_in_house(&mut node_arr, 5, &[45, 51]);
+
+        let steps = EmptyRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![6]);
+    }
+
+    #[test]
+    fn empty_rectangle_on_diagonal() {
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        // Square 0 holds 1 only at r0c0 and r1c1, so both (r0, c1) and
+        // (r1, c0) are empty rectangles. Row 5 holds 1 at c0 and c6, and row
+        // 6 at c1 and c7.
+        keep_in_house(&mut node_arr, 2 * COLOR_COUNT, &[0, 10]);
+        keep_in_house(&mut node_arr, 5, &[45, 51]);
+        keep_in_house(&mut node_arr, 6, &[55, 61]);
+
+        let steps = EmptyRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(eliminated_nodes(&steps), vec![7, 15]);
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer(&SkyscraperEliminator {});
+        check_steps_keep_answer(&TwoStringKiteEliminator {});
+        check_steps_keep_answer(&TurbotFishEliminator {});
+        check_steps_keep_answer(&EmptyRectangleEliminator {});
+    }
+}
@@ -167,7 +167,7 @@ impl ColorSet for ColorBits {
     fn del(&mut self, color: ColorType) -> bool {
         let ret = self.has(color);
         self.colors &= !bit_val(color);
-        return ret;
+        ret
     }
 
     fn has(&self, color: ColorType) -> bool {
@@ -184,6 +184,18 @@ impl ColorSet for ColorBits {
     }
 }
 
+impl ColorBits {
+    // Bit mask of the colors, with bit (c - 1) representing color c.
+    pub fn bits(&self) -> u16 {
+        self.colors
+    }
+}
+
+// Returns the colors of a bit mask in the format of ColorBits::bits.
+pub fn mask_to_colors(mask: u16) -> impl Iterator<Item = ColorType> {
+    (1..=COLOR_COUNT as ColorType).filter(move |c| mask & (1 << (c - 1)) != 0)
+}
+
 impl Debug for ColorBits {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -200,9 +212,9 @@ impl BitOr for ColorBits {
     type Output = Self;
 
     fn bitor(self, rhs: Self) -> Self::Output {
-        return ColorBits {
+        ColorBits {
             colors: self.colors | rhs.colors,
-        };
+        }
     }
 }
 
@@ -273,6 +285,152 @@ impl NodeIdxStack {
     }
 
     pub fn empty(&self) -> bool {
-        return self.cnt == 0;
+        self.cnt == 0
+    }
+}
+
+// Set of nodes, with each bit of a u128 representing a node index.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct NodeBits {
+    bits: u128,
+}
+
+impl NodeBits {
+    pub const fn new() -> Self {
+        NodeBits { bits: 0 }
+    }
+
+    pub fn from_idx_arr(idx_arr: &[NodeIndexType]) -> Self {
+        let mut ret = Self::new();
+        for &idx in idx_arr {
+            ret.set(idx);
+        }
+        ret
+    }
+
+    pub fn set(&mut self, idx: NodeIndexType) {
+        self.bits |= 1 << idx;
+    }
+
+    pub fn del(&mut self, idx: NodeIndexType) -> bool {
+        let ret = self.has(idx);
+        self.bits &= !(1 << idx);
+        ret
+    }
+
+    pub const fn has(&self, idx: NodeIndexType) -> bool {
+        self.bits & (1 << idx) != 0
+    }
+
+    pub const fn count(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub const fn intersect(&self, other: &Self) -> Self {
+        NodeBits {
+            bits: self.bits & other.bits,
+        }
+    }
+
+    pub const fn union(&self, other: &Self) -> Self {
+        NodeBits {
+            bits: self.bits | other.bits,
+        }
+    }
+
+    pub const fn minus(&self, other: &Self) -> Self {
+        NodeBits {
+            bits: self.bits & !other.bits,
+        }
+    }
+
+    // Returns the smallest node index in the set.
+    pub fn first(&self) -> Option<NodeIndexType> {
+        if self.bits == 0 {
+            None
+        } else {
+            Some(self.bits.trailing_zeros() as NodeIndexType)
+        }
+    }
+
+    pub fn iter(&self) -> NodeBitsIter {
+        NodeBitsIter { bits: self.bits }
+    }
+}
+
+impl Debug for NodeBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct NodeBitsIter {
+    bits: u128,
+}
+
+impl Iterator for NodeBitsIter {
+    type Item = NodeIndexType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bits == 0 {
+            return None;
+        }
+        let idx = self.bits.trailing_zeros();
+        self.bits &= self.bits - 1;
+        Some(idx as NodeIndexType)
+    }
+}
+
+const fn build_neighbor_bits_map() -> SudokuArrayType<NodeBits> {
+    let mut ret = [NodeBits::new(); NODE_COUNT];
+    let mut i = 0;
+    while i < NODE_COUNT {
+        let neighs = get_neighs_for_idx(i);
+        let mut j = 0;
+        while j < neighs.len() {
+            ret[i].bits |= 1 << neighs[j];
+            j += 1;
+        }
+        i += 1;
+    }
+    ret
+}
+
+// Same as NEIGHBOR_ARRAY_MAP, but represented as NodeBits.
+pub static NEIGHBOR_BITS_MAP: SudokuArrayType<NodeBits> = build_neighbor_bits_map();
+
+const fn build_house_bits_arr() -> [NodeBits; HOUSE_COUNT] {
+    let mut ret = [NodeBits::new(); HOUSE_COUNT];
+    let mut i = 0;
+    while i < HOUSE_COUNT {
+        let idx_arr = get_all_idx_for_house(i);
+        let mut j = 0;
+        while j < COLOR_COUNT {
+            ret[i].bits |= 1 << idx_arr[j];
+            j += 1;
+        }
+        i += 1;
+    }
+    ret
+}
+
+// Nodes of each house, indexed in the same way as get_all_idx_for_house.
+pub static HOUSE_BITS_ARR: [NodeBits; HOUSE_COUNT] = build_house_bits_arr();
+
+// Returns the uncolored nodes which have `color` as an available color.
+pub fn get_nodes_with_available_color<T: ColorSet>(
+    node_arr: &SolvingNodeArray<T>,
+    color: ColorType,
+) -> NodeBits {
+    let mut ret = NodeBits::new();
+    for (i, node) in node_arr.iter().enumerate() {
+        if node.color == 0 && node.available_colors.has(color) {
+            ret.set(i);
+        }
     }
+    ret
 }
@@ -1,5 +1,6 @@
 use crate::*;
-use itertools::Itertools;
+use fish::FishKind;
+use forcing::ForcingKind;
 use solve_utils::*;
 
 // About score -- we normalize the score in the following way:
@@ -12,11 +13,135 @@ use solve_utils::*;
 
 pub type NodeArray = SolvingNodeArray<ColorBits>;
 
+// A draft number (color) at a node.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Candidate {
+    pub idx: NodeIndexType,
+    pub color: ColorType,
+}
+
+impl Candidate {
+    // Whether the two candidates cannot be the answer at the same time, i.e.
+    // the same color in neighbor nodes, or different colors in the same node.
+    pub fn sees(&self, other: &Candidate) -> bool {
+        if self.idx == other.idx {
+            self.color != other.color
+        } else {
+            self.color == other.color && NEIGHBOR_BITS_MAP[self.idx].has(other.idx)
+        }
+    }
+}
+
+// Techniques with explainable steps.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Technique {
+    Fish {
+        size: usize,
+        finned: bool,
+        kind: FishKind,
+    },
+    XyWing,
+    XyzWing,
+    WWing,
+    WxyzWing,
+    Skyscraper,
+    TwoStringKite,
+    TurbotFish,
+    EmptyRectangle,
+    SimpleColoring,
+    MultiColoring,
+    Medusa,
+    // The chains have the number of their nodes, which are candidates, groups
+    // or ALSs.
+    XChain {
+        length: usize,
+    },
+    XyChain {
+        length: usize,
+    },
+    Aic {
+        length: usize,
+    },
+    GroupedAic {
+        length: usize,
+    },
+    AlsAic {
+        length: usize,
+    },
+    ContinuousNiceLoop {
+        length: usize,
+    },
+    DiscontinuousNiceLoop {
+        length: usize,
+    },
+    UniqueRectangle {
+        type_num: u8,
+    },
+    HiddenUniqueRectangle,
+    AvoidableRectangle {
+        type_num: u8,
+    },
+    BugPlusOne,
+    AlsXz {
+        doubly_linked: bool,
+    },
+    AlsXyWing,
+    AlsChain {
+        length: usize,
+    },
+    DeathBlossom,
+    SueDeCoq,
+    JuniorExocet,
+    SkLoop,
+    Msls,
+    Template {
+        combined: bool,
+    },
+    Forcing {
+        kind: ForcingKind,
+        net: bool,
+    },
+}
+
+// A single deduction made by a solver, which can be shown as a hint.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Step {
+    pub technique: Technique,
+    // Nodes forming the pattern.
+    pub nodes: Vec<NodeIndexType>,
+    // Candidates which are proved to be impossible.
+    pub eliminations: Vec<Candidate>,
+}
+
 // Note that a StrategySolver is usually a partial solver -- it will not solve
 // the puzzle directly, but will eliminate the possible states in that.
 pub trait StrategySolver {
-    // Returns the number of state eliminated.
-    fn work(&self, node_arr: &mut NodeArray) -> i32;
+    // Returns the number of state eliminated. The solvers which can explain
+    // their work apply their steps.
+    fn work(&self, node_arr: &mut NodeArray) -> i32 {
+        let steps = self.find_steps(node_arr);
+        apply_steps(node_arr, &steps)
+    }
+
+    // Returns the deductions available on the board without applying them.
+    // The solvers which cannot explain their work return no step.
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step>;
+}
+
+// Applies the eliminations of all the steps. Since every step only removes
+// candidates which are impossible, the steps found on the same board can be
+// applied together in any order.
+pub fn apply_steps(node_arr: &mut NodeArray, steps: &[Step]) -> i32 {
+    let mut cnt = 0;
+    for step in steps {
+        for e in step.eliminations.iter() {
+            let node = &mut node_arr[e.idx];
+            if node.color == 0 && node.available_colors.del(e.color) {
+                cnt += 1;
+            }
+        }
+    }
+    cnt
 }
 
 fn eliminate_color_at_neighs_to_idx(
@@ -33,7 +158,8 @@ fn eliminate_color_at_neighs_to_idx(
     cnt
 }
 
-fn idx_arr_intersection(
+// Returns the 3 nodes in the intersection of a square and a line.
+pub(crate) fn idx_arr_intersection(
     a: &[NodeIndexType; COLOR_COUNT],
     b: &[NodeIndexType; COLOR_COUNT],
 ) -> [NodeIndexType; 3] {
@@ -49,7 +175,9 @@ fn idx_arr_intersection(
     ret
 }
 
-fn idx_arr_minus(
+// Returns the 6 nodes of a square or a line outside its intersection with the
+// other.
+pub(crate) fn idx_arr_minus(
     a: &[NodeIndexType; COLOR_COUNT],
     b: &[NodeIndexType; COLOR_COUNT],
 ) -> [NodeIndexType; 6] {
@@ -68,6 +196,10 @@ fn idx_arr_minus(
 pub struct BasicEliminator {}
 
 impl StrategySolver for BasicEliminator {
+    fn find_steps(&self, _node_arr: &NodeArray) -> Vec<Step> {
+        Vec::new()
+    }
+
     fn work(&self, node_arr: &mut NodeArray) -> i32 {
         let mut cnt = 0;
         for i in 0..NODE_COUNT {
@@ -83,16 +215,20 @@ impl StrategySolver for BasicEliminator {
 pub struct UniqueDraftValueFiller {}
 
 impl StrategySolver for UniqueDraftValueFiller {
+    fn find_steps(&self, _node_arr: &NodeArray) -> Vec<Step> {
+        Vec::new()
+    }
+
     fn work(&self, node_arr: &mut NodeArray) -> i32 {
         let mut cnt = 0;
-        for i in 0..NODE_COUNT {
-            if node_arr[i].color != 0 {
+        for node in node_arr.iter_mut() {
+            if node.color != 0 {
                 continue;
             }
-            debug_assert_ne!(node_arr[i].available_colors.count(), 0);
-            if let Some(v) = node_arr[i].available_colors.get_unique() {
-                node_arr[i].color = v;
-                node_arr[i].available_colors.clear();
+            debug_assert_ne!(node.available_colors.count(), 0);
+            if let Some(v) = node.available_colors.get_unique() {
+                node.color = v;
+                node.available_colors.clear();
                 cnt += 1;
             }
         }
@@ -100,44 +236,90 @@ impl StrategySolver for UniqueDraftValueFiller {
     }
 }
 
+// Returns the subsets of the bit mask with `size` bits, in the lexicographic
+// order of their bits from the lowest, like the combinations of a list. The
+// order matters, since the eliminations of a subset change the next ones.
+//
+// The combinations are enumerated by Gosper's hack, so only the subsets with
+// the size are visited and nothing is allocated. The complements of the
+// increasing combinations of the other bits are decreasing, which is the
+// lexicographic order once the bits are reversed.
+fn subsets_with_size(mask: u16, size: usize) -> impl Iterator<Item = u16> {
+    let mut bits = [0u16; 16];
+    let mut n = 0;
+    for i in (0..16).rev() {
+        if mask & (1 << i) != 0 {
+            bits[n] = 1 << i;
+            n += 1;
+        }
+    }
+    let full = (1u32 << n) - 1;
+    let mut next = (size <= n).then(|| (1u32 << (n - size)) - 1);
+    std::iter::from_fn(move || {
+        let cur = next?;
+        next = if cur == 0 {
+            None
+        } else {
+            let low = cur & cur.wrapping_neg();
+            let ripple = cur + low;
+            Some((((ripple ^ cur) >> 2) / low) | ripple).filter(|&x| x <= full)
+        };
+        let combination = full ^ cur;
+        Some(
+            (0..n)
+                .filter(|&i| combination & (1 << i) != 0)
+                .fold(0, |ret, i| ret | bits[i]),
+        )
+    })
+}
+
+// Returns the bit mask of the uncolored nodes, with bit i for node_idx[i].
+fn uncolored_mask(node_arr: &NodeArray, node_idx: &[NodeIndexType; COLOR_COUNT]) -> u16 {
+    let mut mask = 0;
+    for (i, &idx) in node_idx.iter().enumerate() {
+        if node_arr[idx].color == 0 {
+            mask |= 1 << i;
+        }
+    }
+    mask
+}
+
 fn eliminate_grouped_colors_from_other_cells(
     node_arr: &mut NodeArray,
     node_idx: &[NodeIndexType; COLOR_COUNT],
     group_size: usize,
 ) -> i32 {
-    let candidates: Vec<usize> = node_idx
-        .into_iter()
-        .filter(|&&x| node_arr[x].color == 0)
-        .map(|&x| x)
-        .collect();
-
-    if candidates.len() <= group_size {
+    let candidates = uncolored_mask(node_arr, node_idx);
+    if candidates.count_ones() as usize <= group_size {
         return 0;
     }
 
     let mut dropped_draft_number_cnt = 0;
 
-    for group in candidates.iter().combinations(group_size) {
+    for group in subsets_with_size(candidates, group_size) {
         let mut super_set = ColorBits::new(false);
-        for &&idx in group.iter() {
-            super_set |= node_arr[idx].available_colors;
+        for (i, &idx) in node_idx.iter().enumerate() {
+            if group & (1 << i) != 0 {
+                super_set |= node_arr[idx].available_colors;
+            }
         }
         if super_set.count() != group_size {
             continue;
         }
-        for &idx in candidates.iter() {
-            if group.iter().find(|&&&x| x == idx).is_some() {
+        for (i, &idx) in node_idx.iter().enumerate() {
+            if (candidates & !group) & (1 << i) == 0 {
                 continue;
             }
-            for c in super_set.get_all() {
-                if node_arr[idx].available_colors.del(c) {
+            let node = &mut node_arr[idx];
+            for c in mask_to_colors(super_set.bits()) {
+                if node.available_colors.del(c) {
                     dropped_draft_number_cnt += 1;
                 }
             }
         }
     }
 
-    return dropped_draft_number_cnt;
+    dropped_draft_number_cnt
 }
 
 fn eliminate_grouped_colors_from_grouped_cells(
@@ -145,55 +327,48 @@ fn eliminate_grouped_colors_from_grouped_cells(
     node_idx: &[NodeIndexType; COLOR_COUNT],
     group_size: usize,
 ) -> i32 {
-    let candidates: Vec<usize> = node_idx
-        .into_iter()
-        .filter(|&&x| node_arr[x].color == 0)
-        .map(|&x| x)
-        .collect();
-
-    if candidates.len() <= group_size {
+    let candidates = uncolored_mask(node_arr, node_idx);
+    if candidates.count_ones() as usize <= group_size {
         return 0;
     }
 
-    let mut color_to_node_idx_idx = [0_u16; COLOR_COUNT + 1];
-    for i in 0..candidates.len() {
-        let idx = candidates[i];
-        for c in node_arr[idx].available_colors.get_all() {
-            color_to_node_idx_idx[c as usize] |= 1 << i;
+    // Bit mask of the nodes for each color, with bit i for node_idx[i].
+    let mut color_to_node_mask = [0_u16; COLOR_COUNT + 1];
+    let mut color_set = 0_u16;
+    for (i, &idx) in node_idx.iter().enumerate() {
+        if candidates & (1 << i) == 0 {
+            continue;
+        }
+        let colors = node_arr[idx].available_colors.bits();
+        color_set |= colors;
+        for c in mask_to_colors(colors) {
+            color_to_node_mask[c as usize] |= 1 << i;
         }
-    }
-
-    let mut color_set = ColorBits::new(false);
-    for idx in candidates.iter() {
-        color_set |= node_arr[*idx].available_colors;
     }
 
     let mut dropped_draft_number_cnt: i32 = 0;
-    for group in color_set.get_all().into_iter().combinations(group_size) {
+    for group in subsets_with_size(color_set, group_size) {
         let mut super_set = 0_u16;
-        for &c in group.iter() {
-            super_set |= color_to_node_idx_idx[c as usize];
+        for c in mask_to_colors(group) {
+            super_set |= color_to_node_mask[c as usize];
         }
         if super_set.count_ones() != group_size as u32 {
             continue;
         }
-        for i in 0..COLOR_COUNT {
+        for (i, &idx) in node_idx.iter().enumerate() {
             if (1 << i) & super_set == 0 {
                 continue;
             }
-            let node = &mut node_arr[candidates[i]];
-            for c in 1..COLOR_COUNT + 1 {
-                if group.contains(&(c as u8)) {
-                    continue;
-                }
-                if node.available_colors.del(c as u8) {
+            let node = &mut node_arr[idx];
+            for c in mask_to_colors(!group & ((1 << COLOR_COUNT) - 1)) {
+                if node.available_colors.del(c) {
                     dropped_draft_number_cnt += 1;
                 }
             }
         }
     }
 
-    return dropped_draft_number_cnt;
+    dropped_draft_number_cnt
 }
 
 pub struct NonHiddenGroupEliminator {
@@ -201,6 +376,10 @@ pub struct NonHiddenGroupEliminator {
 }
 
 impl StrategySolver for NonHiddenGroupEliminator {
+    fn find_steps(&self, _node_arr: &NodeArray) -> Vec<Step> {
+        Vec::new()
+    }
+
     fn work(&self, node_arr: &mut NodeArray) -> i32 {
         let mut ret = 0;
         for i in 0..9 {
@@ -230,6 +409,10 @@ pub struct HiddenGroupEliminator {
 }
 
 impl StrategySolver for HiddenGroupEliminator {
+    fn find_steps(&self, _node_arr: &NodeArray) -> Vec<Step> {
+        Vec::new()
+    }
+
     fn work(&self, node_arr: &mut NodeArray) -> i32 {
         let mut ret = 0;
         for i in 0..9 {
@@ -315,6 +498,10 @@ fn eliminate_by_intersection(
 }
 
 impl StrategySolver for IntersectionEliminator {
+    fn find_steps(&self, _node_arr: &NodeArray) -> Vec<Step> {
+        Vec::new()
+    }
+
     fn work(&self, node_arr: &mut NodeArray) -> i32 {
         let mut cnt = 0;
         for i in 0..COLOR_COUNT {
@@ -342,8 +529,8 @@ mod tests {
     fn hidden_group_eliminator_group_1() {
         let board = [0u8; NODE_COUNT];
         let mut node_arr = NodeArray::from_color_array(&board);
-        for i in 1..9 {
-            node_arr[i].available_colors.del(1);
+        for node in node_arr[1..9].iter_mut() {
+            node.available_colors.del(1);
         }
 
         let solver = HiddenGroupEliminator { group_size: 1 };
@@ -355,9 +542,9 @@ mod tests {
     fn hidden_group_eliminator_group_2() {
         let board = [0u8; NODE_COUNT];
         let mut node_arr = NodeArray::from_color_array(&board);
-        for i in 1..8 {
-            node_arr[i].available_colors.del(1);
-            node_arr[i].available_colors.del(2);
+        for node in node_arr[1..8].iter_mut() {
+            node.available_colors.del(1);
+            node.available_colors.del(2);
         }
 
         let solver = HiddenGroupEliminator { group_size: 2 };
@@ -390,17 +577,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hidden_group_eliminator_group_4() {
+        let board = [0u8; NODE_COUNT];
+        let mut node_arr = NodeArray::from_color_array(&board);
+        for node in node_arr[4..9].iter_mut() {
+            for color in 1..5 {
+                node.available_colors.del(color);
+            }
+        }
+
+        let solver = HiddenGroupEliminator { group_size: 4 };
+        assert!(solver.work(&mut node_arr) > 0);
+        for node in node_arr[0..4].iter() {
+            assert_eq!(node.available_colors.get_all(), vec![1, 2, 3, 4]);
+        }
+        assert_eq!(node_arr[9].available_colors.count(), 9);
+    }
+
+    #[test]
+    fn non_hidden_group_eliminator_group_4() {
+        let board = [0u8; NODE_COUNT];
+        let mut node_arr = NodeArray::from_color_array(&board);
+        for &idx in [0, 2, 4, 6].iter() {
+            for color in 5..10 {
+                node_arr[idx].available_colors.del(color);
+            }
+        }
+
+        let solver = NonHiddenGroupEliminator { group_size: 4 };
+        assert_eq!(solver.work(&mut node_arr), 5 * 4);
+        assert_eq!(node_arr[0].available_colors.get_all(), vec![1, 2, 3, 4]);
+        assert_eq!(node_arr[1].available_colors.get_all(), vec![5, 6, 7, 8, 9]);
+        assert_eq!(node_arr[9].available_colors.count(), 9);
+    }
+
+    #[test]
+    fn subsets_with_size() {
+        let subsets = super::subsets_with_size(0b1011, 2).collect::<Vec<_>>();
+        assert_eq!(subsets, vec![0b0011, 0b1001, 0b1010]);
+        assert_eq!(super::subsets_with_size(0b1011, 4).count(), 0);
+        assert_eq!(
+            super::subsets_with_size(0b1011, 3).collect::<Vec<_>>(),
+            vec![0b1011]
+        );
+        assert_eq!(
+            super::subsets_with_size(0b1011, 0).collect::<Vec<_>>(),
+            vec![0]
+        );
+        let quads = super::subsets_with_size(0x1ff, 4).collect::<Vec<_>>();
+        assert_eq!(quads.len(), 126);
+        assert!(quads.iter().all(|q| q.count_ones() == 4));
+        assert_eq!((quads[0], quads[1], quads[125]), (0x00f, 0x017, 0x1e0));
+    }
+
     #[test]
     fn intersection_eliminator() {
         let board = [0u8; NODE_COUNT];
         let mut node_arr = NodeArray::from_color_array(&board);
-        for i in 3..COLOR_COUNT {
-            node_arr[i].available_colors.del(1);
+        for node in node_arr[3..COLOR_COUNT].iter_mut() {
+            node.available_colors.del(1);
         }
 
         let solver = IntersectionEliminator {};
         assert!(solver.work(&mut node_arr) > 0);
-        for &i in get_all_idx_for_sqr(0)[3..].into_iter() {
+        for &i in get_all_idx_for_sqr(0)[3..].iter() {
             assert_eq!(
                 node_arr[i].available_colors.get_all(),
                 vec![2, 3, 4, 5, 6, 7, 8, 9]
@@ -0,0 +1,216 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Sue de Coq works on the intersection of a square and a line. Pick n >= 2
+// nodes C in the intersection, a set of nodes A in the rest of the line, and
+// a set of nodes B in the rest of the square, where no color of A is a color
+// of B. If the nodes of C, A and B hold exactly as many colors as there are
+// nodes, each of the colors is the answer of exactly one of the nodes: a color
+// of A can only be the answer once in C and A since they are all in the line,
+// a color of B only once in C and B, and any other color only once in C.
+//
+// So the colors of A, and the colors of C which are not in B, must be in the
+// line, and can be eliminated from the other nodes of the line. Same for the
+// square. The classic form uses a bivalue node as A and B, and the extended
+// forms use larger sets.
+
+// Max number of nodes of A and B each.
+const MAX_SET_SIZE: usize = 3;
+
+fn colors_of(node_arr: &NodeArray, nodes: &[NodeIndexType]) -> u16 {
+    nodes
+        .iter()
+        .fold(0, |acc, &idx| acc | node_arr[idx].available_colors.bits())
+}
+
+// Returns the non-empty subsets of the uncolored nodes with at most max_size
+// nodes.
+fn subsets(
+    node_arr: &NodeArray,
+    idx_arr: &[NodeIndexType],
+    max_size: usize,
+) -> Vec<Vec<NodeIndexType>> {
+    let idx_arr = idx_arr
+        .iter()
+        .copied()
+        .filter(|&idx| node_arr[idx].color == 0)
+        .collect::<Vec<_>>();
+    let mut ret = Vec::new();
+    for mask in 1..(1u32 << idx_arr.len()) {
+        if mask.count_ones() as usize > max_size {
+            continue;
+        }
+        ret.push(
+            (0..idx_arr.len())
+                .filter(|i| mask & (1 << i) != 0)
+                .map(|i| idx_arr[i])
+                .collect(),
+        );
+    }
+    ret
+}
+
+// Returns the sets sharing colors with C, with their colors.
+fn useful_sets<'a>(
+    node_arr: &NodeArray,
+    sets: &'a [Vec<NodeIndexType>],
+    c_colors: u16,
+) -> Vec<(&'a [NodeIndexType], u16)> {
+    sets.iter()
+        .map(|set| (&set[..], colors_of(node_arr, set)))
+        .filter(|(_, colors)| colors & c_colors != 0)
+        .collect()
+}
+
+pub struct SueDeCoqEliminator {}
+
+impl SueDeCoqEliminator {
+    fn find_in_intersection(
+        &self,
+        node_arr: &NodeArray,
+        sqr: &[NodeIndexType; COLOR_COUNT],
+        line: &[NodeIndexType; COLOR_COUNT],
+        steps: &mut Vec<Step>,
+    ) {
+        let intersection = idx_arr_intersection(sqr, line);
+        let line_rest = idx_arr_minus(line, sqr);
+        let sqr_rest = idx_arr_minus(sqr, line);
+        let line_sets = subsets(node_arr, &line_rest, MAX_SET_SIZE);
+        let sqr_sets = subsets(node_arr, &sqr_rest, MAX_SET_SIZE);
+
+        for c in subsets(node_arr, &intersection, 3) {
+            let c_colors = colors_of(node_arr, &c);
+            if c.len() < 2 || (c_colors.count_ones() as usize) < c.len() + 2 {
+                continue;
+            }
+            // The sets in A and B must share colors with C, or they add as
+            // many colors as nodes and do not help.
+            let line_sets = useful_sets(node_arr, &line_sets, c_colors);
+            let sqr_sets = useful_sets(node_arr, &sqr_sets, c_colors);
+            for &(a, a_colors) in line_sets.iter() {
+                let ca_count = (c_colors | a_colors).count_ones() as usize;
+                if ca_count > c.len() + a.len() + MAX_SET_SIZE {
+                    continue;
+                }
+                for &(b, b_colors) in sqr_sets.iter() {
+                    let all_colors = c_colors | a_colors | b_colors;
+                    if a_colors & b_colors != 0
+                        || all_colors.count_ones() as usize != c.len() + a.len() + b.len()
+                    {
+                        continue;
+                    }
+                    let line_colors = a_colors | (c_colors & !b_colors);
+                    let sqr_colors = b_colors | (c_colors & !a_colors);
+                    let mut eliminations = Vec::new();
+                    let mut eliminate = |idx_arr: &[NodeIndexType], colors: u16| {
+                        for &idx in idx_arr {
+                            if c.contains(&idx) || a.contains(&idx) || b.contains(&idx) {
+                                continue;
+                            }
+                            let node = &node_arr[idx];
+                            for color in mask_to_colors(colors) {
+                                if node.color == 0 && node.available_colors.has(color) {
+                                    eliminations.push(Candidate { idx, color });
+                                }
+                            }
+                        }
+                    };
+                    eliminate(line, line_colors);
+                    eliminate(sqr, sqr_colors);
+                    if eliminations.is_empty() {
+                        continue;
+                    }
+                    eliminations.sort();
+                    eliminations.dedup();
+                    steps.push(Step {
+                        technique: Technique::SueDeCoq,
+                        nodes: [&c[..], a, b].concat(),
+                        eliminations,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl StrategySolver for SueDeCoqEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for i in 0..COLOR_COUNT {
+            let sqr = get_all_idx_for_sqr(i);
+            for j in 0..COLOR_COUNT {
+                if i % 3 == j / 3 {
+                    let col = get_all_idx_for_col(j);
+                    self.find_in_intersection(node_arr, &sqr, &col, &mut steps);
+                }
+                if i / 3 == j / 3 {
+                    let row = get_all_idx_for_row(j);
+                    self.find_in_intersection(node_arr, &sqr, &row, &mut steps);
+                }
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    fn colors_at(idx_arr: &[NodeIndexType], colors: &[ColorType]) -> Vec<Candidate> {
+        let mut ret = Vec::new();
+        for &idx in idx_arr {
+            for &color in colors {
+                ret.push(Candidate { idx, color });
+            }
+        }
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn sue_de_coq() {
+        // C: r0c0 and r0c1 {1, 2, 3, 4}, A: r0c5 {1, 2}, B: r1c1 {3, 4}.
+        let node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2, 3, 4]),
+            (1, &[1, 2, 3, 4]),
+            (5, &[1, 2]),
+            (10, &[3, 4]),
+        ]);
+        let steps = SueDeCoqEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].nodes, vec![0, 1, 5, 10]);
+        let mut expected = colors_at(&[2, 3, 4, 6, 7, 8], &[1, 2]);
+        expected.append(&mut colors_at(&[2, 9, 11, 18, 19, 20], &[3, 4]));
+        expected.sort();
+        assert_eq!(steps[0].eliminations, expected);
+    }
+
+    #[test]
+    fn extended_sue_de_coq() {
+        // Same as above, but B is the ALS r1c1 {3, 5} and r2c1 {4, 5}.
+        let node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2, 3, 4]),
+            (1, &[1, 2, 3, 4]),
+            (5, &[1, 2]),
+            (10, &[3, 5]),
+            (19, &[4, 5]),
+        ]);
+        let steps = SueDeCoqEliminator {}.find_steps(&node_arr);
+        let step = steps
+            .iter()
+            .find(|s| s.nodes == vec![0, 1, 5, 10, 19])
+            .unwrap();
+        let mut expected = colors_at(&[2, 3, 4, 6, 7, 8], &[1, 2]);
+        expected.append(&mut colors_at(&[2, 9, 11, 18, 20], &[3, 4, 5]));
+        expected.sort();
+        assert_eq!(step.eliminations, expected);
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer(&SueDeCoqEliminator {});
+    }
+}
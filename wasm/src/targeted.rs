@@ -0,0 +1,152 @@
+use crate::*;
+use generator::*;
+use rating::*;
+use scorer::ScorerConfig;
+
+// Generation of puzzles which require a technique, e.g. for training packs.
+// A puzzle requires the technique if the solve which always uses the easiest
+// steps uses it, and nothing harder is needed. Since the easier techniques
+// only eliminate candidates, they cannot avoid the technique by another order.
+
+// The uniqueness and exotic techniques can be targeted too, since the
+// generated puzzles have unique answers.
+const TARGET_CONFIG: ScorerConfig = ScorerConfig {
+    assume_unique: true,
+    exotic: true,
+    chains: true,
+};
+
+// Returns how many times the puzzle uses the technique, if it requires it and
+// nothing harder. The technique is the name of a pipeline stage, or one of
+// "last_digit", "hidden_single" and "naked_single".
+pub fn technique_uses(puzzle: &ColorArray, technique: &str) -> Option<usize> {
+    let max_rating = technique_rating(technique)?;
+    let solve = rated_solve_up_to(puzzle, &TARGET_CONFIG, max_rating, None);
+    if !solve.solved {
+        return None;
+    }
+    let mut uses = 0;
+    for step in solve.steps.iter() {
+        if step.name == technique {
+            uses += 1;
+        } else if step.rating > max_rating {
+            return None;
+        }
+    }
+    if uses == 0 {
+        return None;
+    }
+    // Another stage with the same rating may solve the puzzle as well, but
+    // only the first one is used. The singles are always used.
+    if stage_rating(technique).is_some()
+        && rated_solve_up_to(puzzle, &TARGET_CONFIG, max_rating, Some(technique)).solved
+    {
+        return None;
+    }
+    Some(uses)
+}
+
+// Tries of generate_puzzle_with_technique made in a second, about.
+const TECHNIQUE_TRIES_PER_SEC: f64 = 5.0;
+
+// Number of tries of generate_puzzle_with_technique without a timeout.
+const MAX_TECHNIQUE_TRIES: usize = 1000;
+
+// Generates puzzles until one uses the technique at least min_uses times and
+// needs nothing harder. Returns None if none is found before the timeout of
+// the config or after MAX_TECHNIQUE_TRIES tries, or if the technique is
+// unknown. Each try uses the next seed. With a seed, the timeout
+// is turned into a number of tries, see TryBudget.
+pub fn generate_puzzle_with_technique(
+    technique: &str,
+    min_uses: usize,
+    config: GeneratorConfig,
+) -> Option<ColorArray> {
+    technique_rating(technique)?;
+    let mut budget = TryBudget::new(&config, TECHNIQUE_TRIES_PER_SEC);
+    for i in 0..MAX_TECHNIQUE_TRIES as u64 {
+        if budget.is_exhausted() {
+            break;
+        }
+        let puzzle = generate_puzzle(GeneratorConfig {
+            timeout: budget.nested_timeout(),
+            seed: config.seed.map(|seed| seed.wrapping_add(i)),
+            ..config
+        });
+        budget.add_try();
+        if technique_uses(&puzzle, technique).is_some_and(|uses| uses >= min_uses) {
+            return Some(puzzle);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use test_utils::*;
+
+    #[test]
+    fn technique_uses_of_puzzle() {
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        assert!(technique_uses(&puzzle, "wxyz_wing").is_some_and(|uses| uses > 0));
+        // Harder techniques are needed.
+        assert_eq!(technique_uses(&puzzle, "x_wing"), None);
+        assert_eq!(technique_uses(&puzzle, "naked_single"), None);
+        // Not needed.
+        assert_eq!(technique_uses(&puzzle, "medusa"), None);
+        assert_eq!(technique_uses(&puzzle, "no_such_technique"), None);
+
+        // The xyz-wing is found first, but a technique with the same rating
+        // solves the puzzle without it.
+        let puzzle = parse_sudoku_array(
+            "16.....2....3.94...........8....53..4.........9..1.5.6..2.8...7.......5398...1...",
+        )
+        .unwrap();
+        let solve = rated_solve_up_to(&puzzle, &TARGET_CONFIG, 44, None);
+        assert!(solve.solved);
+        assert!(solve.steps.iter().any(|s| s.name == "xyz_wing"));
+        assert_eq!(technique_uses(&puzzle, "xyz_wing"), None);
+    }
+
+    #[test]
+    fn generate_with_technique() {
+        let config = GeneratorConfig {
+            timeout: Some(Duration::from_secs(20)),
+            target_clues_num: 26,
+            seed: Some(11),
+            ..Default::default()
+        };
+        let puzzle = generate_puzzle_with_technique("intersection", 2, config).unwrap();
+        assert!(technique_uses(&puzzle, "intersection").unwrap() >= 2);
+        assert_eq!(
+            generate_puzzle_with_technique("no_such_technique", 1, config),
+            None
+        );
+    }
+
+    #[test]
+    fn generate_with_technique_gives_up() {
+        let config = GeneratorConfig {
+            timeout: Some(Duration::ZERO),
+            seed: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_puzzle_with_technique("junior_exocet", 1, config),
+            None
+        );
+        // Without a timeout, the tries are limited.
+        let config = GeneratorConfig {
+            timeout: None,
+            target_clues_num: 30,
+            seed: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_puzzle_with_technique("junior_exocet", 1, config),
+            None
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use crate::*;
+use solve_utils::*;
+use std::collections::HashSet;
+use strategy_solver::*;
+
+// Pattern overlay, a.k.a. templates. A template of a color is a way to place
+// the color in all the 9 rows, with one node in each column and each square.
+// The templates consistent with the board are enumerated, then a candidate in
+// no template is eliminated, and a candidate in all of them is the answer, so
+// the other colors of its node are eliminated.
+//
+// Combining the templates of two colors, a template of the first color is
+// only kept if some template of the second color has no node in common with
+// it, since the two colors cannot be the answers of the same node.
+
+// Max number of template pairs to check when combining two colors.
+const MAX_COMBINATIONS: usize = 1 << 16;
+
+// Returns the templates of the color which are consistent with the board.
+pub fn templates_for_color(node_arr: &NodeArray, color: ColorType) -> Vec<NodeBits> {
+    let mut given_neighbors = NodeBits::new();
+    for (idx, node) in node_arr.iter().enumerate() {
+        if node.color == color {
+            given_neighbors = given_neighbors.union(&NEIGHBOR_BITS_MAP[idx]);
+        }
+    }
+    // Bit mask of the columns where the color can be placed, for each row.
+    let mut allowed_cols = [0u16; COLOR_COUNT];
+    for (idx, node) in node_arr.iter().enumerate() {
+        let allowed = if node.color == 0 {
+            node.available_colors.has(color) && !given_neighbors.has(idx)
+        } else {
+            node.color == color
+        };
+        if allowed {
+            allowed_cols[row_idx(idx)] |= 1 << col_idx(idx);
+        }
+    }
+
+    let mut templates = Vec::new();
+    search_templates(&allowed_cols, 0, 0, 0, NodeBits::new(), &mut templates);
+    templates
+}
+
+fn search_templates(
+    allowed_cols: &[u16; COLOR_COUNT],
+    row: usize,
+    used_cols: u16,
+    used_sqrs: u16,
+    template: NodeBits,
+    templates: &mut Vec<NodeBits>,
+) {
+    if row == COLOR_COUNT {
+        templates.push(template);
+        return;
+    }
+    let cols = allowed_cols[row] & !used_cols;
+    for col in (0..COLOR_COUNT).filter(|&col| cols & (1 << col) != 0) {
+        let idx = row * COLOR_COUNT + col;
+        let sqr = sqr_idx(idx);
+        if used_sqrs & (1 << sqr) != 0 {
+            continue;
+        }
+        let mut next = template;
+        next.set(idx);
+        search_templates(
+            allowed_cols,
+            row + 1,
+            used_cols | (1 << col),
+            used_sqrs | (1 << sqr),
+            next,
+            templates,
+        );
+    }
+}
+
+pub struct TemplateEliminator {
+    // Whether to combine the templates of each pair of colors.
+    pub combine_pairs: bool,
+}
+
+impl TemplateEliminator {
+    fn step_for_templates(
+        &self,
+        node_arr: &NodeArray,
+        color: ColorType,
+        templates: &[NodeBits],
+        combined: bool,
+    ) -> Option<Step> {
+        if templates.is_empty() {
+            // The puzzle has no answer.
+            return None;
+        }
+        let mut in_any = NodeBits::new();
+        let mut in_all = templates[0];
+        for template in templates.iter() {
+            in_any = in_any.union(template);
+            in_all = in_all.intersect(template);
+        }
+
+        let mut nodes = Vec::new();
+        let mut eliminations = Vec::new();
+        for (idx, node) in node_arr.iter().enumerate() {
+            if node.color != 0 || !node.available_colors.has(color) {
+                continue;
+            }
+            nodes.push(idx);
+            if !in_any.has(idx) {
+                eliminations.push(Candidate { idx, color });
+            } else if in_all.has(idx) {
+                for other in mask_to_colors(node.available_colors.bits()) {
+                    if other != color {
+                        eliminations.push(Candidate { idx, color: other });
+                    }
+                }
+            }
+        }
+        if eliminations.is_empty() {
+            return None;
+        }
+        Some(Step {
+            technique: Technique::Template { combined },
+            nodes,
+            eliminations,
+        })
+    }
+}
+
+impl StrategySolver for TemplateEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let templates = (1..=COLOR_COUNT as ColorType)
+            .map(|color| templates_for_color(node_arr, color))
+            .collect::<Vec<_>>();
+        let mut steps = Vec::new();
+        for (i, color_templates) in templates.iter().enumerate() {
+            let color = i as ColorType + 1;
+            steps.extend(self.step_for_templates(node_arr, color, color_templates, false));
+        }
+        if !self.combine_pairs {
+            return steps;
+        }
+
+        let mut eliminated = steps
+            .iter()
+            .flat_map(|s| s.eliminations.iter().copied())
+            .collect::<HashSet<_>>();
+        for (i, first) in templates.iter().enumerate() {
+            for (j, second) in templates.iter().enumerate() {
+                if i == j || first.len() * second.len() > MAX_COMBINATIONS {
+                    continue;
+                }
+                let kept = first
+                    .iter()
+                    .copied()
+                    .filter(|t| second.iter().any(|u| t.intersect(u).is_empty()))
+                    .collect::<Vec<_>>();
+                if kept.len() == first.len() {
+                    continue;
+                }
+                let color = i as ColorType + 1;
+                if let Some(mut step) = self.step_for_templates(node_arr, color, &kept, true) {
+                    step.eliminations.retain(|&e| eliminated.insert(e));
+                    if !step.eliminations.is_empty() {
+                        steps.push(step);
+                    }
+                }
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn templates_of_empty_board() {
+        let node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        assert_eq!(templates_for_color(&node_arr, 1).len(), 46656);
+    }
+
+    #[test]
+    fn templates_contain_answer() {
+        for src in TEST_PUZZLES.iter() {
+            let puzzle = parse_sudoku_array(src).unwrap();
+            let answer = solve_unique(&puzzle);
+            let mut node_arr = NodeArray::from_color_array(&puzzle);
+            apply_basic_solvers(&mut node_arr);
+            for color in 1..=COLOR_COUNT as ColorType {
+                let idx_arr = (0..NODE_COUNT)
+                    .filter(|&idx| answer[idx] == color)
+                    .collect::<Vec<_>>();
+                let templates = templates_for_color(&node_arr, color);
+                assert!(templates.contains(&NodeBits::from_idx_arr(&idx_arr)));
+            }
+        }
+    }
+
+    #[test]
+    fn x_wing_by_templates() {
+        // Color 1 of rows 0 and 4 is only at columns 0 and 4.
+        let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+        for row in [0, 4] {
+            for col in [1, 2, 3, 5, 6, 7, 8] {
+                node_arr[row * COLOR_COUNT + col].available_colors.del(1);
+            }
+        }
+        let steps = TemplateEliminator {
+            combine_pairs: false,
+        }
+        .find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        let mut expected = Vec::new();
+        for row in [1, 2, 3, 5, 6, 7, 8] {
+            for col in [0, 4] {
+                expected.push(Candidate {
+                    idx: row * COLOR_COUNT + col,
+                    color: 1,
+                });
+            }
+        }
+        expected.sort();
+        assert_eq!(steps[0].eliminations, expected);
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        let eliminator = |combine_pairs| TemplateEliminator { combine_pairs };
+        check_steps_keep_answer(&eliminator(false));
+        check_steps_keep_answer(&eliminator(true));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::*;
+use fast_solver::solve;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Puzzles with unique answers, which need techniques beyond the basic ones.
+pub const TEST_PUZZLES: [&str; 11] = [
+    ".5.....48...69..3..1..8....6....3..41....2.5....9..76..712..3.......5.......7.8..",
+    "......2.8..5.36..73.1.........4.7.1.......4729........8.2...5....619....5..86....",
+    "...2..6.17....1..2...8.9....94....5.6.27..........3..4...95.....63.....884.3.....",
+    "....4.87.........394...75..31...............1..26..9..2..3..6...6.5182.......9..8",
+    ".3..5..8...1..6...5..2...9...9....5...7.4...2.1....7...4.9.7.....8....34....631..",
+    "...7.9..........1..5..8....6...43....4.85...3.3.6..8.2..75..2..2......6...4.1..9.",
+    // The puzzles below are extremely hard.
+    "4...3.......6..8..........1....5..9..8....6...7.2........1.27..5.3....4.9........",
+    "8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..",
+    "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..",
+    "1.......2.9.4...5...6...7...5.9.3.......7.......85..4.7.....6...3...9.8...2.....1",
+    "..............3.85..1.2.......5.7.....4...1...9.......5......73..2.1........4...9",
+];
+
+pub fn solve_unique(puzzle: &ColorArray) -> ColorArray {
+    match solve(puzzle) {
+        SolveResult::Unique(answer) => answer,
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+// Applies the basic solvers until they get stuck. Restarts from the first
+// solver after any progress, so that newly filled colors are always eliminated
+// from the neighbors before the other solvers run.
+pub fn apply_basic_solvers(node_arr: &mut NodeArray) {
+    let solvers: [&dyn StrategySolver; 5] = [
+        &BasicEliminator {},
+        &UniqueDraftValueFiller {},
+        &HiddenGroupEliminator { group_size: 1 },
+        &IntersectionEliminator {},
+        &NonHiddenGroupEliminator { group_size: 2 },
+    ];
+    while solvers.iter().any(|s| s.work(node_arr) > 0) {}
+}
+
+// Runs the solver on the test puzzles together with the basic solvers, and
+// checks that some steps are found and that the answer is never eliminated.
+// Returns the number of steps found.
+pub fn check_steps_keep_answer(solver: &dyn StrategySolver) -> usize {
+    check_steps_keep_answer_on(&TEST_PUZZLES, solver)
+}
+
+// Same as check_steps_keep_answer, on the given puzzles instead of the test
+// puzzles, for the patterns which none of them has.
+pub fn check_steps_keep_answer_on(puzzles: &[&str], solver: &dyn StrategySolver) -> usize {
+    let step_cnt = puzzles
+        .iter()
+        .map(|src| check_puzzle_steps_keep_answer(src, solver))
+        .sum();
+    assert!(step_cnt > 0, "No step found");
+    step_cnt
+}
+
+fn check_puzzle_steps_keep_answer(src: &str, solver: &dyn StrategySolver) -> usize {
+    let mut step_cnt = 0;
+    let puzzle = parse_sudoku_array(src).unwrap();
+    let answer = solve_unique(&puzzle);
+    let mut node_arr = NodeArray::from_color_array(&puzzle);
+    loop {
+        apply_basic_solvers(&mut node_arr);
+        let steps = solver.find_steps(&node_arr);
+        for step in steps.iter() {
+            for e in step.eliminations.iter() {
+                assert_ne!(answer[e.idx], e.color, "{:?}", step);
+            }
+        }
+        step_cnt += steps.len();
+        if apply_steps(&mut node_arr, &steps) == 0 {
+            break;
+        }
+    }
+    step_cnt
+}
+
+// Returns a board without any clue, with the given candidates kept at nodes.
+pub fn node_arr_with_candidates(candidates: &[(NodeIndexType, &[ColorType])]) -> NodeArray {
+    let mut node_arr = NodeArray::from_color_array(&[0; NODE_COUNT]);
+    for &(idx, colors) in candidates {
+        node_arr[idx].available_colors = ColorBits::new(false);
+        for &c in colors {
+            node_arr[idx].available_colors.set(c);
+        }
+    }
+    node_arr
+}
@@ -0,0 +1,749 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Techniques which assume the puzzle has a unique answer. They are only valid
+// for such puzzles, so the scorer does not use them unless asked to.
+//
+// A deadly pattern is a rectangle of nodes in two rows, two columns and two
+// squares, holding two colors {a, b} as a on one diagonal and b on the other.
+// Swapping a and b gives another answer, so if the answer is unique, such a
+// pattern cannot be formed by the unsolved nodes, nor by the nodes solved
+// from the puzzle since they are not givens.
+
+// Returns the rectangles of nodes in two rows, two columns and two squares,
+// as [r1c1, r1c2, r2c1, r2c2].
+fn rectangles() -> Vec<[NodeIndexType; 4]> {
+    let mut ret = Vec::new();
+    for r1 in 0..COLOR_COUNT {
+        for r2 in r1 + 1..COLOR_COUNT {
+            for c1 in 0..COLOR_COUNT {
+                for c2 in c1 + 1..COLOR_COUNT {
+                    if (r1 / 3 == r2 / 3) == (c1 / 3 == c2 / 3) {
+                        continue;
+                    }
+                    let idx = |r, c| r * COLOR_COUNT + c;
+                    ret.push([idx(r1, c1), idx(r1, c2), idx(r2, c1), idx(r2, c2)]);
+                }
+            }
+        }
+    }
+    ret
+}
+
+// Returns the corner opposite to rect[k].
+fn opposite(k: usize) -> usize {
+    3 - k
+}
+
+fn color_bit(color: ColorType) -> u16 {
+    1 << (color - 1)
+}
+
+// Returns the houses containing both nodes.
+fn common_houses(a: NodeIndexType, b: NodeIndexType) -> Vec<usize> {
+    let houses_b = get_houses_for_idx(b);
+    get_houses_for_idx(a)
+        .into_iter()
+        .filter(|h| houses_b.contains(h))
+        .collect()
+}
+
+// Returns the uncolored nodes seeing all the given nodes, which have color
+// available.
+fn common_peers(node_arr: &NodeArray, nodes: &[NodeIndexType], color: ColorType) -> NodeBits {
+    nodes.iter().fold(
+        get_nodes_with_available_color(node_arr, color),
+        |acc, &idx| acc.intersect(&NEIGHBOR_BITS_MAP[idx]),
+    )
+}
+
+fn to_candidates(nodes: &NodeBits, color: ColorType) -> Vec<Candidate> {
+    nodes.iter().map(|idx| Candidate { idx, color }).collect()
+}
+
+struct RectangleSearch<'a> {
+    node_arr: &'a NodeArray,
+    rect: [NodeIndexType; 4],
+    masks: [u16; 4],
+    a: ColorType,
+    b: ColorType,
+    // Corners holding only a and b, and the other corners.
+    floor: Vec<usize>,
+    roof: Vec<usize>,
+    steps: Vec<Step>,
+}
+
+impl RectangleSearch<'_> {
+    fn push_step(&mut self, type_num: u8, eliminations: Vec<Candidate>) {
+        self.push_step_with(Technique::UniqueRectangle { type_num }, eliminations);
+    }
+
+    fn push_step_with(&mut self, technique: Technique, eliminations: Vec<Candidate>) {
+        let eliminations = eliminations
+            .into_iter()
+            .filter(|c| self.node_arr[c.idx].available_colors.has(c.color))
+            .collect::<Vec<_>>();
+        if eliminations.is_empty() {
+            return;
+        }
+        self.steps.push(Step {
+            technique,
+            nodes: self.rect.to_vec(),
+            eliminations,
+        });
+    }
+
+    fn extras(&self, k: usize) -> u16 {
+        self.masks[k] & !(color_bit(self.a) | color_bit(self.b))
+    }
+
+    // One roof: it cannot be a or b.
+    fn type_1(&mut self) {
+        if self.roof.len() != 1 {
+            return;
+        }
+        let idx = self.rect[self.roof[0]];
+        let eliminations = vec![
+            Candidate { idx, color: self.a },
+            Candidate { idx, color: self.b },
+        ];
+        self.push_step(1, eliminations);
+    }
+
+    // Roofs with the same single extra color c: one of them must be c. Type 2
+    // has two roofs in the same line, and type 5 has diagonal roofs or three
+    // roofs.
+    fn type_2_and_5(&mut self) {
+        if self.roof.len() < 2 {
+            return;
+        }
+        let extras = self.extras(self.roof[0]);
+        if extras.count_ones() != 1 || self.roof.iter().any(|&k| self.extras(k) != extras) {
+            return;
+        }
+        let c = mask_to_colors(extras).next().unwrap();
+        let roof_nodes = self.roof.iter().map(|&k| self.rect[k]).collect::<Vec<_>>();
+        let eliminations = to_candidates(&common_peers(self.node_arr, &roof_nodes, c), c);
+        let is_line = roof_nodes.len() == 2 && opposite(self.roof[0]) != self.roof[1];
+        self.push_step(if is_line { 2 } else { 5 }, eliminations);
+    }
+
+    // Two roofs in the same house. Their extra colors act as a single node,
+    // which can form a naked subset with the other nodes in the house.
+    fn type_3(&mut self) {
+        if self.roof.len() != 2 {
+            return;
+        }
+        let (p, q) = (self.rect[self.roof[0]], self.rect[self.roof[1]]);
+        let extras = self.extras(self.roof[0]) | self.extras(self.roof[1]);
+        for house in common_houses(p, q) {
+            let others = get_all_idx_for_house(house)
+                .into_iter()
+                .filter(|&idx| self.node_arr[idx].color == 0 && !self.rect.contains(&idx))
+                .collect::<Vec<_>>();
+            self.search_subset(&others, 0, &mut Vec::new(), extras);
+        }
+    }
+
+    fn search_subset(
+        &mut self,
+        others: &[NodeIndexType],
+        start: usize,
+        subset: &mut Vec<NodeIndexType>,
+        colors: u16,
+    ) {
+        if !subset.is_empty() && colors.count_ones() as usize == subset.len() + 1 {
+            let mut eliminations = Vec::new();
+            for &idx in others.iter().filter(|idx| !subset.contains(idx)) {
+                for color in mask_to_colors(colors) {
+                    eliminations.push(Candidate { idx, color });
+                }
+            }
+            self.push_step(3, eliminations);
+            return;
+        }
+        // Subsets with more than three nodes are rarely useful.
+        if subset.len() == 3 {
+            return;
+        }
+        for i in start..others.len() {
+            let union = colors | self.node_arr[others[i]].available_colors.bits();
+            if union.count_ones() as usize > 4 {
+                continue;
+            }
+            subset.push(others[i]);
+            self.search_subset(others, i + 1, subset, union);
+            subset.pop();
+        }
+    }
+
+    // Two roofs in the same house, which is the only place for a in the house.
+    // One of them must be a, so if the other one were b, they would form the
+    // deadly pattern with the floors. So neither can be b.
+    fn type_4(&mut self) {
+        if self.roof.len() != 2 {
+            return;
+        }
+        let (p, q) = (self.rect[self.roof[0]], self.rect[self.roof[1]]);
+        for house in common_houses(p, q) {
+            for (x, y) in [(self.a, self.b), (self.b, self.a)] {
+                let holders = get_nodes_with_available_color(self.node_arr, x)
+                    .intersect(&HOUSE_BITS_ARR[house]);
+                if holders == NodeBits::from_idx_arr(&[p, q]) {
+                    let eliminations = vec![
+                        Candidate { idx: p, color: y },
+                        Candidate { idx: q, color: y },
+                    ];
+                    self.push_step(4, eliminations);
+                }
+            }
+        }
+    }
+
+    // Diagonal roofs, with a only in the rectangle on both rows or on both
+    // columns. If a roof is a, the other roof is a as well, forming the deadly
+    // pattern.
+    fn type_6(&mut self) {
+        if self.roof.len() != 2 || opposite(self.roof[0]) != self.roof[1] {
+            return;
+        }
+        let rect_bits = NodeBits::from_idx_arr(&self.rect);
+        for x in [self.a, self.b] {
+            let candidates = get_nodes_with_available_color(self.node_arr, x);
+            let locked_in = |houses: [usize; 2]| {
+                houses.iter().all(|&h| {
+                    candidates
+                        .intersect(&HOUSE_BITS_ARR[h])
+                        .minus(&rect_bits)
+                        .is_empty()
+                })
+            };
+            let rows = [row_idx(self.rect[0]), row_idx(self.rect[3])];
+            let cols = [
+                COLOR_COUNT + col_idx(self.rect[0]),
+                COLOR_COUNT + col_idx(self.rect[3]),
+            ];
+            if locked_in(rows) || locked_in(cols) {
+                let eliminations = self
+                    .roof
+                    .iter()
+                    .map(|&k| Candidate {
+                        idx: self.rect[k],
+                        color: x,
+                    })
+                    .collect();
+                self.push_step(6, eliminations);
+            }
+        }
+    }
+
+    // A floor F whose opposite corner D is a roof. If a is only in the
+    // rectangle on both the row and the column of D, D cannot be b: otherwise
+    // the other two corners are a, so F is b.
+    fn hidden(&mut self) {
+        let rect_bits = NodeBits::from_idx_arr(&self.rect);
+        for f in self.floor.clone() {
+            let d = opposite(f);
+            if self.floor.contains(&d) {
+                continue;
+            }
+            let idx = self.rect[d];
+            for (x, y) in [(self.a, self.b), (self.b, self.a)] {
+                let candidates = get_nodes_with_available_color(self.node_arr, x);
+                let locked = [row_idx(idx), COLOR_COUNT + col_idx(idx)].iter().all(|&h| {
+                    candidates
+                        .intersect(&HOUSE_BITS_ARR[h])
+                        .minus(&rect_bits)
+                        .is_empty()
+                });
+                if locked {
+                    self.push_step_with(
+                        Technique::HiddenUniqueRectangle,
+                        vec![Candidate { idx, color: y }],
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub struct UniqueRectangleEliminator {}
+
+impl StrategySolver for UniqueRectangleEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for rect in rectangles() {
+            if rect.iter().any(|&idx| node_arr[idx].color != 0) {
+                continue;
+            }
+            let masks = rect.map(|idx| node_arr[idx].available_colors.bits());
+            let common = masks.iter().fold(u16::MAX, |acc, m| acc & m);
+            let colors = mask_to_colors(common).collect::<Vec<_>>();
+            for (i, &a) in colors.iter().enumerate() {
+                for &b in colors[i + 1..].iter() {
+                    let ab = color_bit(a) | color_bit(b);
+                    let (floor, roof): (Vec<usize>, Vec<usize>) =
+                        (0..4).partition(|&k| masks[k] == ab);
+                    if floor.is_empty() {
+                        continue;
+                    }
+                    let mut search = RectangleSearch {
+                        node_arr,
+                        rect,
+                        masks,
+                        a,
+                        b,
+                        floor,
+                        roof,
+                        steps: Vec::new(),
+                    };
+                    search.type_1();
+                    search.type_2_and_5();
+                    search.type_3();
+                    search.type_4();
+                    search.type_6();
+                    search.hidden();
+                    steps.append(&mut search.steps);
+                }
+            }
+        }
+        steps
+    }
+}
+
+// Bivalue universal grave (BUG): if all the unsolved nodes are bivalue, and
+// each color is available in either zero or two nodes of each house, the
+// puzzle has zero or multiple answers. With BUG+1, all the unsolved nodes are
+// bivalue except one node with three colors, and removing one of its colors x
+// leads to a BUG. So that node must be x.
+pub struct BugPlusOneEliminator {}
+
+impl StrategySolver for BugPlusOneEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut plus_one = None;
+        for (idx, node) in node_arr.iter().enumerate() {
+            if node.color != 0 {
+                continue;
+            }
+            match node.available_colors.count() {
+                2 => {}
+                3 if plus_one.is_none() => plus_one = Some(idx),
+                _ => return Vec::new(),
+            }
+        }
+        let idx = match plus_one {
+            Some(idx) => idx,
+            None => return Vec::new(),
+        };
+
+        let colors = node_arr[idx].available_colors.get_all();
+        for &x in colors.iter() {
+            let is_bug = (1..=COLOR_COUNT as ColorType).all(|color| {
+                let mut candidates = get_nodes_with_available_color(node_arr, color);
+                if color == x {
+                    candidates.del(idx);
+                }
+                HOUSE_BITS_ARR.iter().all(|house| {
+                    let count = candidates.intersect(house).count();
+                    count == 0 || count == 2
+                })
+            });
+            if is_bug {
+                return vec![Step {
+                    technique: Technique::BugPlusOne,
+                    nodes: vec![idx],
+                    eliminations: colors
+                        .iter()
+                        .filter(|&&c| c != x)
+                        .map(|&color| Candidate { idx, color })
+                        .collect(),
+                }];
+            }
+        }
+        Vec::new()
+    }
+}
+
+// Avoidable rectangles are formed by nodes solved from the puzzle, which are
+// not givens, and so must not form a deadly pattern either.
+// - Type 1: three corners are solved as a, b and b, where a is opposite to the
+//   unsolved corner D. D cannot be a.
+// - Type 2: two corners in a line are solved as a and b, and the other
+//   corners are {a, c} and {b, c}, opposite to a and b respectively. One of
+//   them must be c.
+pub struct AvoidableRectangleEliminator {
+    pub givens: NodeBits,
+}
+
+impl AvoidableRectangleEliminator {
+    fn solved_color(&self, node_arr: &NodeArray, idx: NodeIndexType) -> Option<ColorType> {
+        if node_arr[idx].color == 0 || self.givens.has(idx) {
+            None
+        } else {
+            Some(node_arr[idx].color)
+        }
+    }
+}
+
+impl StrategySolver for AvoidableRectangleEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let mut push_step = |rect: &[NodeIndexType; 4], type_num, eliminations: Vec<Candidate>| {
+            if !eliminations.is_empty() {
+                steps.push(Step {
+                    technique: Technique::AvoidableRectangle { type_num },
+                    nodes: rect.to_vec(),
+                    eliminations,
+                });
+            }
+        };
+        for rect in rectangles() {
+            let solved = rect.map(|idx| self.solved_color(node_arr, idx));
+
+            // Type 1.
+            for d in 0..4 {
+                let idx = rect[d];
+                let (p, q) = if d == 0 || d == 3 { (1, 2) } else { (0, 3) };
+                if node_arr[idx].color != 0 {
+                    continue;
+                }
+                if let (Some(a), Some(b), Some(b2)) = (solved[opposite(d)], solved[p], solved[q]) {
+                    if a != b && b == b2 && node_arr[idx].available_colors.has(a) {
+                        push_step(&rect, 1, vec![Candidate { idx, color: a }]);
+                    }
+                }
+            }
+
+            // Type 2.
+            for (x, y) in [(0, 1), (2, 3), (0, 2), (1, 3)] {
+                let (a, b) = match (solved[x], solved[y]) {
+                    (Some(a), Some(b)) if a != b => (a, b),
+                    _ => continue,
+                };
+                // The corners which would be a and b in the deadly pattern.
+                let (da, db) = (rect[opposite(x)], rect[opposite(y)]);
+                if node_arr[da].color != 0 || node_arr[db].color != 0 {
+                    continue;
+                }
+                let c_mask = node_arr[da].available_colors.bits() & !color_bit(a);
+                if c_mask.count_ones() != 1
+                    || node_arr[da].available_colors.bits() != c_mask | color_bit(a)
+                    || node_arr[db].available_colors.bits() != c_mask | color_bit(b)
+                {
+                    continue;
+                }
+                let c = mask_to_colors(c_mask).next().unwrap();
+                let eliminations = common_peers(node_arr, &[da, db], c);
+                push_step(&rect, 2, to_candidates(&eliminations, c));
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scorer::ScorerConfig;
+    use test_utils::*;
+
+    // The rectangle r0c0, r0c3, r1c0 and r1c3 is used below.
+    const RECT: [NodeIndexType; 4] = [0, 3, 9, 12];
+
+    fn del_color_in_house(node_arr: &mut NodeArray, house: usize, color: ColorType) {
+        for idx in get_all_idx_for_house(house) {
+            if !RECT.contains(&idx) {
+                node_arr[idx].available_colors.del(color);
+            }
+        }
+    }
+
+    fn colors_at(idx_arr: &[NodeIndexType], color: ColorType) -> Vec<Candidate> {
+        idx_arr
+            .iter()
+            .map(|&idx| Candidate { idx, color })
+            .collect()
+    }
+
+    #[test]
+    fn unique_rectangle_type_1() {
+        let node_arr =
+            node_arr_with_candidates(&[(0, &[1, 2]), (3, &[1, 2]), (9, &[1, 2]), (12, &[1, 2, 3])]);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::UniqueRectangle { type_num: 1 }
+        );
+        assert_eq!(
+            steps[0].eliminations,
+            vec![
+                Candidate { idx: 12, color: 1 },
+                Candidate { idx: 12, color: 2 }
+            ]
+        );
+    }
+
+    #[test]
+    fn unique_rectangle_type_2() {
+        let node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2]),
+            (3, &[1, 2]),
+            (9, &[1, 2, 5]),
+            (12, &[1, 2, 5]),
+        ]);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::UniqueRectangle { type_num: 2 }
+        );
+        assert_eq!(
+            steps[0].eliminations,
+            colors_at(&[10, 11, 13, 14, 15, 16, 17], 5)
+        );
+    }
+
+    #[test]
+    fn unique_rectangle_type_3() {
+        let node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2]),
+            (3, &[1, 2]),
+            (9, &[1, 2, 5]),
+            (12, &[1, 2, 6]),
+            (10, &[5, 6]),
+        ]);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::UniqueRectangle { type_num: 3 }
+        );
+        let expected = [11, 13, 14, 15, 16, 17]
+            .iter()
+            .flat_map(|&idx| colors_at(&[idx], 5).into_iter().chain(colors_at(&[idx], 6)))
+            .collect::<Vec<_>>();
+        assert_eq!(steps[0].eliminations, expected);
+    }
+
+    #[test]
+    fn unique_rectangle_type_4() {
+        let mut node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2]),
+            (3, &[1, 2]),
+            (9, &[1, 2, 5]),
+            (12, &[1, 2, 6]),
+        ]);
+        // Row 1 holds 1 only at c0 and c3.
+        del_color_in_house(&mut node_arr, 1, 1);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::UniqueRectangle { type_num: 4 }
+        );
+        assert_eq!(steps[0].eliminations, colors_at(&[9, 12], 2));
+    }
+
+    #[test]
+    fn unique_rectangle_type_5() {
+        let node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2, 5]),
+            (3, &[1, 2]),
+            (9, &[1, 2]),
+            (12, &[1, 2, 5]),
+        ]);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::UniqueRectangle { type_num: 5 }
+        );
+        assert_eq!(steps[0].eliminations, colors_at(&[4, 5, 10, 11], 5));
+    }
+
+    #[test]
+    fn unique_rectangle_type_6() {
+        let mut node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2, 5]),
+            (3, &[1, 2]),
+            (9, &[1, 2]),
+            (12, &[1, 2, 6]),
+        ]);
+        // Rows 0 and 1 hold 1 only in the rectangle.
+        del_color_in_house(&mut node_arr, 0, 1);
+        del_color_in_house(&mut node_arr, 1, 1);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::UniqueRectangle { type_num: 6 }
+        );
+        assert_eq!(steps[0].eliminations, colors_at(&[0, 12], 1));
+    }
+
+    #[test]
+    fn hidden_unique_rectangle() {
+        let mut node_arr = node_arr_with_candidates(&[(0, &[1, 2])]);
+        // Row 1 and column 3 hold 1 only in the rectangle.
+        del_color_in_house(&mut node_arr, 1, 1);
+        del_color_in_house(&mut node_arr, COLOR_COUNT + 3, 1);
+        let steps = UniqueRectangleEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::HiddenUniqueRectangle);
+        assert_eq!(steps[0].eliminations, colors_at(&[12], 2));
+    }
+
+    #[test]
+    fn bug_plus_one() {
+        // Solve a puzzle, and leave a BUG on r0c0-2 and r3c0-2 with the colors
+        // {a, b, c} of r0c0-2. Adding a to r0c0 makes it a BUG+1.
+        let puzzle = parse_sudoku_array(TEST_PUZZLES[0]).unwrap();
+        let mut node_arr = NodeArray::from_color_array(&solve_unique(&puzzle));
+        let [a, b, c] = [node_arr[0].color, node_arr[1].color, node_arr[2].color];
+        let bits = |colors: &[ColorType]| {
+            let mut ret = ColorBits::new(false);
+            for &color in colors {
+                ret.set(color);
+            }
+            ret
+        };
+        let bug = [
+            (0, bits(&[a, b, c])),
+            (1, bits(&[a, b])),
+            (2, bits(&[a, c])),
+            (27, bits(&[b, c])),
+            (28, bits(&[a, b])),
+            (29, bits(&[a, c])),
+        ];
+        for (idx, colors) in bug {
+            node_arr[idx].color = 0;
+            node_arr[idx].available_colors = colors;
+        }
+
+        let steps = BugPlusOneEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        let mut eliminations = steps[0].eliminations.clone();
+        eliminations.sort();
+        let mut expected = vec![
+            Candidate { idx: 0, color: b },
+            Candidate { idx: 0, color: c },
+        ];
+        expected.sort();
+        assert_eq!(eliminations, expected);
+    }
+
+    #[test]
+    fn avoidable_rectangle_type_1() {
+        let mut puzzle = [0; NODE_COUNT];
+        puzzle[0] = 1;
+        puzzle[3] = 2;
+        puzzle[9] = 2;
+        let node_arr = NodeArray::from_color_array(&puzzle);
+
+        let solver = AvoidableRectangleEliminator {
+            givens: NodeBits::new(),
+        };
+        let steps = solver.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].eliminations, colors_at(&[12], 1));
+
+        // No elimination if any of the corners is a given.
+        let solver = AvoidableRectangleEliminator {
+            givens: NodeBits::from_idx_arr(&[3]),
+        };
+        assert!(solver.find_steps(&node_arr).is_empty());
+    }
+
+    #[test]
+    fn avoidable_rectangle_type_2() {
+        let mut puzzle = [0; NODE_COUNT];
+        puzzle[0] = 1;
+        puzzle[3] = 2;
+        let mut node_arr = NodeArray::from_color_array(&puzzle);
+        // The corner opposite to 1 is {1, 3}, and the one opposite to 2 is
+        // {2, 3}.
+        node_arr[12].available_colors = ColorBits::new(false);
+        node_arr[12].available_colors.set(1);
+        node_arr[12].available_colors.set(3);
+        node_arr[9].available_colors = ColorBits::new(false);
+        node_arr[9].available_colors.set(2);
+        node_arr[9].available_colors.set(3);
+
+        let solver = AvoidableRectangleEliminator {
+            givens: NodeBits::new(),
+        };
+        let steps = solver.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].technique,
+            Technique::AvoidableRectangle { type_num: 2 }
+        );
+        assert_eq!(
+            steps[0].eliminations,
+            colors_at(&[10, 11, 13, 14, 15, 16, 17], 3)
+        );
+    }
+
+    // Puzzles left as a BUG+1 by the basic solvers.
+    const BUG_PLUS_ONE_PUZZLES: [&str; 2] = [
+        ".3.....2...782...4......86.9...8..1...647......1........9..635..5..47....1......9",
+        "..4...7....1.9.2.....268....57.8....1....4...3...1.6..9.......8.4..2...3..57.3...",
+    ];
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer(&UniqueRectangleEliminator {});
+        check_steps_keep_answer_on(&BUG_PLUS_ONE_PUZZLES, &BugPlusOneEliminator {});
+    }
+
+    // An avoidable rectangle only shows up after some advanced steps, so the
+    // puzzle is solved by the hints of the pipeline instead of the basic
+    // solvers alone.
+    #[test]
+    fn avoidable_rectangle_keeps_answer() {
+        let puzzle = parse_sudoku_array(
+            "..3.8..469..2.......1.3...5...62...46.....9...94....3....7.5.8.......1..17.......",
+        )
+        .unwrap();
+        let answer = solve_unique(&puzzle);
+        let givens = (0..NODE_COUNT)
+            .filter(|&idx| puzzle[idx] != 0)
+            .collect::<Vec<_>>();
+        let solver = AvoidableRectangleEliminator {
+            givens: NodeBits::from_idx_arr(&givens),
+        };
+        let config = ScorerConfig {
+            chains: true,
+            ..Default::default()
+        };
+        let pipeline = PipelineBuilder::from_config(&puzzle, &config).build();
+        let mut node_arr = NodeArray::from_color_array(&puzzle);
+        let mut step_cnt = 0;
+        loop {
+            apply_basic_solvers(&mut node_arr);
+            let steps = solver.find_steps(&node_arr);
+            for step in steps.iter() {
+                for e in step.eliminations.iter() {
+                    assert_ne!(answer[e.idx], e.color, "{:?}", step);
+                }
+            }
+            step_cnt += steps.len();
+            if apply_steps(&mut node_arr, &steps) > 0 {
+                continue;
+            }
+            match pipeline.hint(&node_arr) {
+                Some((stage, steps)) if steps.is_empty() => {
+                    stage.solver.work(&mut node_arr);
+                }
+                Some((_, steps)) => {
+                    apply_steps(&mut node_arr, &steps);
+                }
+                None => break,
+            }
+        }
+        assert!(step_cnt > 0);
+        assert_eq!(node_arr.to_color_array(), answer);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::*;
+use solve_utils::*;
+use strategy_solver::*;
+
+// Most wings are sets of n cells holding exactly n candidates in total. A
+// candidate is restricted if all the cells holding it see each other, so it
+// can be the answer of at most one cell in the set. If only one candidate z
+// is not restricted, the other candidates can fill at most n - 1 cells, so
+// one of the cells holding z must be z. Then z can be eliminated from the
+// cells which see all the cells holding z in the set.
+
+// Returns the common peers of all the nodes holding color.
+fn common_peers(node_arr: &NodeArray, nodes: &[NodeIndexType], color: ColorType) -> NodeBits {
+    let mut ret = get_nodes_with_available_color(node_arr, color);
+    for &idx in nodes {
+        if node_arr[idx].available_colors.has(color) {
+            ret = ret.intersect(&NEIGHBOR_BITS_MAP[idx]);
+        }
+    }
+    ret
+}
+
+struct WingSearch<'a> {
+    node_arr: &'a NodeArray,
+    masks: [u16; NODE_COUNT],
+    size: usize,
+    technique: Technique,
+    // Checks the candidate masks of the cells, e.g. all bivalue for XY-Wing.
+    accept: fn(&[u16]) -> bool,
+    steps: Vec<Step>,
+}
+
+impl WingSearch<'_> {
+    fn search(&mut self, start: NodeIndexType, nodes: &mut Vec<NodeIndexType>, union: u16) {
+        if nodes.len() == self.size {
+            if union.count_ones() as usize == self.size {
+                self.check(nodes);
+            }
+            return;
+        }
+        for idx in start..NODE_COUNT {
+            let mask = self.masks[idx];
+            if mask == 0 || (union | mask).count_ones() as usize > self.size {
+                continue;
+            }
+            nodes.push(idx);
+            self.search(idx + 1, nodes, union | mask);
+            nodes.pop();
+        }
+    }
+
+    fn check(&mut self, nodes: &[NodeIndexType]) {
+        let masks = nodes.iter().map(|&idx| self.masks[idx]).collect::<Vec<_>>();
+        if !(self.accept)(&masks) {
+            return;
+        }
+        let union = masks.iter().fold(0, |acc, m| acc | m);
+
+        let mut non_restricted = None;
+        for c in mask_to_colors(union) {
+            let holders = nodes
+                .iter()
+                .filter(|&&idx| self.masks[idx] & (1 << (c - 1)) != 0)
+                .collect::<Vec<_>>();
+            let restricted = holders.iter().enumerate().all(|(i, &&a)| {
+                holders[i + 1..]
+                    .iter()
+                    .all(|&&b| NEIGHBOR_BITS_MAP[a].has(b))
+            });
+            if restricted {
+                continue;
+            }
+            if non_restricted.is_some() {
+                return;
+            }
+            non_restricted = Some(c);
+        }
+        let z = match non_restricted {
+            Some(z) => z,
+            None => return,
+        };
+
+        let eliminations = common_peers(self.node_arr, nodes, z);
+        if eliminations.is_empty() {
+            return;
+        }
+        self.steps.push(Step {
+            technique: self.technique,
+            nodes: nodes.to_vec(),
+            eliminations: eliminations
+                .iter()
+                .map(|idx| Candidate { idx, color: z })
+                .collect(),
+        });
+    }
+}
+
+fn find_wings(
+    node_arr: &NodeArray,
+    size: usize,
+    technique: Technique,
+    accept: fn(&[u16]) -> bool,
+) -> Vec<Step> {
+    let mut masks = [0; NODE_COUNT];
+    for (idx, mask) in masks.iter_mut().enumerate() {
+        let node = &node_arr[idx];
+        if node.color == 0 && node.available_colors.count() <= size {
+            *mask = node.available_colors.bits();
+        }
+    }
+    let mut search = WingSearch {
+        node_arr,
+        masks,
+        size,
+        technique,
+        accept,
+        steps: Vec::new(),
+    };
+    search.search(0, &mut Vec::new(), 0);
+    search.steps
+}
+
+// Pivot {x, y} with pincers {x, z} and {y, z}.
+pub struct XyWingEliminator {}
+
+impl StrategySolver for XyWingEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        find_wings(node_arr, 3, Technique::XyWing, |masks| {
+            masks.iter().all(|m| m.count_ones() == 2)
+        })
+    }
+}
+
+// Pivot {x, y, z} with pincers {x, z} and {y, z}.
+pub struct XyzWingEliminator {}
+
+impl StrategySolver for XyzWingEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        find_wings(node_arr, 3, Technique::XyzWing, |masks| {
+            let mut counts = masks.iter().map(|m| m.count_ones()).collect::<Vec<_>>();
+            counts.sort();
+            counts == [2, 2, 3]
+        })
+    }
+}
+
+// Four cells holding four candidates, one of which is not restricted.
+pub struct WxyzWingEliminator {}
+
+impl StrategySolver for WxyzWingEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        find_wings(node_arr, 4, Technique::WxyzWing, |_| true)
+    }
+}
+
+// Two cells {x, y} which do not see each other, connected by a strong link on
+// x, i.e. a house holding x in only two cells, each of which sees one of the
+// {x, y} cells. One of the two cells must be y, so y can be eliminated from
+// the cells seeing both.
+pub struct WWingEliminator {}
+
+impl StrategySolver for WWingEliminator {
+    fn find_steps(&self, node_arr: &NodeArray) -> Vec<Step> {
+        let bivalue_nodes = (0..NODE_COUNT)
+            .filter(|&idx| node_arr[idx].color == 0 && node_arr[idx].available_colors.count() == 2)
+            .collect::<Vec<_>>();
+
+        let mut steps = Vec::new();
+        for (i, &a) in bivalue_nodes.iter().enumerate() {
+            for &b in bivalue_nodes[i + 1..].iter() {
+                if node_arr[a].available_colors != node_arr[b].available_colors
+                    || NEIGHBOR_BITS_MAP[a].has(b)
+                {
+                    continue;
+                }
+                let colors = node_arr[a].available_colors.get_all();
+                for (x, y) in [(colors[0], colors[1]), (colors[1], colors[0])] {
+                    let eliminations = NEIGHBOR_BITS_MAP[a]
+                        .intersect(&NEIGHBOR_BITS_MAP[b])
+                        .intersect(&get_nodes_with_available_color(node_arr, y));
+                    if eliminations.is_empty() {
+                        continue;
+                    }
+                    let x_nodes = get_nodes_with_available_color(node_arr, x);
+                    for house in HOUSE_BITS_ARR.iter() {
+                        let link = house.intersect(&x_nodes);
+                        if link.count() != 2 || link.has(a) || link.has(b) {
+                            continue;
+                        }
+                        let mut link_nodes = link.iter();
+                        let c = link_nodes.next().unwrap();
+                        let d = link_nodes.next().unwrap();
+                        let connected = (NEIGHBOR_BITS_MAP[a].has(c)
+                            && NEIGHBOR_BITS_MAP[b].has(d))
+                            || (NEIGHBOR_BITS_MAP[a].has(d) && NEIGHBOR_BITS_MAP[b].has(c));
+                        if !connected {
+                            continue;
+                        }
+                        steps.push(Step {
+                            technique: Technique::WWing,
+                            nodes: vec![a, b, c, d],
+                            eliminations: eliminations
+                                .iter()
+                                .map(|idx| Candidate { idx, color: y })
+                                .collect(),
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn xy_wing() {
+        // Pivot r0c0 {1, 2}, pincers r0c5 {1, 3} and r4c0 {2, 3}.
+        let node_arr = node_arr_with_candidates(&[(0, &[1, 2]), (5, &[1, 3]), (36, &[2, 3])]);
+        let steps = XyWingEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].nodes, vec![0, 5, 36]);
+        assert_eq!(steps[0].eliminations, vec![Candidate { idx: 41, color: 3 }]);
+        assert!(XyzWingEliminator {}.find_steps(&node_arr).is_empty());
+    }
+
+    #[test]
+    fn xyz_wing() {
+        // Pivot r0c0 {1, 2, 3}, pincers r0c7 {1, 3} and r1c1 {2, 3}.
+        let node_arr = node_arr_with_candidates(&[(0, &[1, 2, 3]), (7, &[1, 3]), (10, &[2, 3])]);
+        let steps = XyzWingEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].eliminations,
+            vec![
+                Candidate { idx: 1, color: 3 },
+                Candidate { idx: 2, color: 3 }
+            ]
+        );
+    }
+
+    #[test]
+    fn wxyz_wing() {
+        // Pivot r0c0 {1, 2, 3, 4}, pincers r0c7 {1, 4}, r1c1 {2, 4} and
+        // r2c2 {3, 4}.
+        let node_arr = node_arr_with_candidates(&[
+            (0, &[1, 2, 3, 4]),
+            (7, &[1, 4]),
+            (10, &[2, 4]),
+            (20, &[3, 4]),
+        ]);
+        let steps = WxyzWingEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].eliminations,
+            vec![
+                Candidate { idx: 1, color: 4 },
+                Candidate { idx: 2, color: 4 }
+            ]
+        );
+    }
+
+    #[test]
+    fn w_wing() {
+        // r0c0 and r4c4 are {1, 2}. Row 8 holds 1 only at c0 and c4.
+        let mut candidates: Vec<(NodeIndexType, &[ColorType])> = vec![(0, &[1, 2]), (40, &[1, 2])];
+        for idx in get_all_idx_for_row(8) {
+            if idx != 72 && idx != 76 {
+                candidates.push((idx, &[2, 3, 4, 5, 6, 7, 8, 9]));
+            }
+        }
+        let node_arr = node_arr_with_candidates(&candidates);
+        let steps = WWingEliminator {}.find_steps(&node_arr);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].nodes, vec![0, 40, 72, 76]);
+        assert_eq!(
+            steps[0].eliminations,
+            vec![
+                Candidate { idx: 4, color: 2 },
+                Candidate { idx: 36, color: 2 }
+            ]
+        );
+    }
+
+    #[test]
+    fn eliminations_keep_answer() {
+        check_steps_keep_answer(&XyWingEliminator {});
+        check_steps_keep_answer(&XyzWingEliminator {});
+        check_steps_keep_answer(&WWingEliminator {});
+        check_steps_keep_answer(&WxyzWingEliminator {});
+    }
+}